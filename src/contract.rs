@@ -1,295 +1,3976 @@
-use crate::model::{Access, DataKey, Error, OrderRec, Package, Session};
+use crate::model::{
+    Access, ContractConfig, DataKey, DataKeyExt, Error, FeeTiming, GrantSource, LegacyOrderRec,
+    LegacySession, OrderRec, OrderStatus, Package, Pass, PromoCode, SaleRules, Session, SessionV2,
+    TimePass, ERROR_CODE_COUNT, EVENT_SCHEMA_VERSION, SESSION_SCHEMA_VERSION,
+};
 
 use soroban_sdk::{
-    contract, contractimpl, panic_with_error, symbol_short, token::Client as TokenClient, Address,
-    Env, Symbol,
+    contract, contractimpl, panic_with_error, symbol_short, token::Client as TokenClient,
+    xdr::ToXdr, Address, Bytes, BytesN, Env, IntoVal, Map, String, Symbol, TryFromVal, Val, Vec,
 };
 
 // -------------------------------------------------------------
 // TIPOS AUXILIARES (somente neste arquivo)
 // -------------------------------------------------------------
 
-// storage helpers p/ OrderRec (persistent)
-fn load_order(env: &Env, owner: &Address, order_id: u128) -> Option<OrderRec> {
+/// Base dos percentuais em basis points (10000 = 100%).
+const BPS_DENOMINATOR: i128 = 10_000;
+
+/// Teto padrão de `duration_secs` (365 dias) enquanto o admin não configura outro.
+const DEFAULT_MAX_PACKAGE_DURATION_SECS: u32 = 365 * 24 * 60 * 60;
+
+/// XDR de `Asset::Native` (discriminante 0), usado para resolver o SAC do XLM.
+const NATIVE_ASSET_XDR: [u8; 4] = [0; 4];
+
+/// Duração do dia UTC usado pelo teto diário de consumo.
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Máximo de entradas aceitas pelas operações em lote.
+const MAX_BATCH: u32 = 50;
+
+/// TTL da instância em ledgers (~5s cada): renova para 30 dias ao cair abaixo de 7.
+const INSTANCE_TTL_THRESHOLD: u32 = 7 * 17_280;
+const INSTANCE_TTL_EXTEND_TO: u32 = 30 * 17_280;
+
+fn load_token(env: &Env) -> Address {
+    env.storage()
+        .instance()
+        .get(&DataKey::Token)
+        .unwrap_or_else(|| panic_with_error!(env, Error::NotInitialized))
+}
+
+fn load_admin(env: &Env) -> Address {
+    env.storage()
+        .instance()
+        .get(&DataKey::Admin)
+        .unwrap_or_else(|| panic_with_error!(env, Error::NotInitialized))
+}
+
+// carrega o admin e exige a assinatura dele
+fn require_admin(env: &Env) -> Address {
+    let admin = load_admin(env);
+    admin.require_auth();
+    admin
+}
+
+// destino dos pagamentos; se não configurado, o próprio admin
+fn treasury(env: &Env) -> Address {
+    env.storage()
+        .instance()
+        .get(&DataKey::Treasury)
+        .unwrap_or_else(|| load_admin(env))
+}
+
+fn splits(env: &Env) -> Vec<(Address, u32)> {
+    env.storage()
+        .instance()
+        .get(&DataKey::Splits)
+        .unwrap_or_else(|| Vec::new(env))
+}
+
+fn reserve_bps(env: &Env) -> u32 {
+    env.storage()
+        .instance()
+        .get(&DataKey::ReserveBps)
+        .unwrap_or(0)
+}
+
+fn reserve_balance(env: &Env) -> i128 {
+    env.storage()
+        .instance()
+        .get(&DataKey::ReserveBalance)
+        .unwrap_or(0)
+}
+
+// reserva retida em `token`: ReserveBalance guarda a do token atual e
+// ReserveOf(token) a de tokens anteriores (trocados no `set_token`)
+fn reserve_in(env: &Env, token: &Address) -> i128 {
+    if *token == load_token(env) {
+        return reserve_balance(env);
+    }
+    env.storage()
+        .instance()
+        .get(&DataKeyExt::ReserveOf(token.clone()))
+        .unwrap_or(0)
+}
+fn set_reserve_in(env: &Env, token: &Address, amount: i128) {
+    if *token == load_token(env) {
+        env.storage()
+            .instance()
+            .set(&DataKey::ReserveBalance, &amount);
+    } else if amount == 0 {
+        env.storage()
+            .instance()
+            .remove(&DataKeyExt::ReserveOf(token.clone()));
+    } else {
+        env.storage()
+            .instance()
+            .set(&DataKeyExt::ReserveOf(token.clone()), &amount);
+    }
+}
+
+// paga `amount` de `from`: a fração ReserveBps fica retida no contrato e o resto
+// vai à tesouraria ou, se houver Splits, a cada recebedor na proporção do seu
+// peso; a sobra da divisão inteira fica com o primeiro recebedor ou, com
+// RoundingToTreasury desligado, com o coletor da taxa.
+fn pay_out(env: &Env, token: &TokenClient, from: &Address, amount: i128) {
+    let reserve = amount * reserve_bps(env) as i128 / BPS_DENOMINATOR;
+    if reserve > 0 {
+        let this = env.current_contract_address();
+        if *from != this {
+            token.transfer(from, &this, &reserve);
+        }
+        set_reserve_in(
+            env,
+            &token.address,
+            reserve_in(env, &token.address) + reserve,
+        );
+    }
+    let amount = amount - reserve;
+    if amount == 0 {
+        return;
+    }
+    let splits = splits(env);
+    if splits.is_empty() {
+        token.transfer(from, &treasury(env), &amount);
+        return;
+    }
+    let mut shares: Vec<i128> = Vec::new(env);
+    let mut distributed: i128 = 0;
+    for (_, bps) in splits.iter() {
+        let share = amount * bps as i128 / BPS_DENOMINATOR;
+        shares.push_back(share);
+        distributed += share;
+    }
+    let leftover = amount - distributed;
+    let to_first = rounding_to_treasury(env);
+    for (i, (to, _)) in splits.iter().enumerate() {
+        let mut share = shares.get_unchecked(i as u32);
+        if i == 0 && to_first {
+            share += leftover;
+        }
+        if share > 0 {
+            token.transfer(from, &to, &share);
+        }
+    }
+    if !to_first && leftover > 0 {
+        token.transfer(from, &fee_collector(env), &leftover);
+    }
+}
+
+fn fee_collector(env: &Env) -> Address {
+    env.storage()
+        .instance()
+        .get(&DataKey::FeeCollector)
+        .unwrap_or_else(|| treasury(env))
+}
+
+fn fee_bps(env: &Env) -> u32 {
+    env.storage().instance().get(&DataKey::FeeBps).unwrap_or(0)
+}
+
+fn rounding_to_treasury(env: &Env) -> bool {
+    env.storage()
+        .instance()
+        .get(&DataKeyExt::RoundingToTreasury)
+        .unwrap_or(true)
+}
+
+// taxa sobre `price`: arredonda para baixo (sobra com a tesouraria) ou para
+// cima (sobra com o coletor), conforme RoundingToTreasury
+fn fee_of(env: &Env, price: i128) -> i128 {
+    let raw = price * fee_bps(env) as i128;
+    if rounding_to_treasury(env) {
+        raw / BPS_DENOMINATOR
+    } else {
+        (raw + BPS_DENOMINATOR - 1) / BPS_DENOMINATOR
+    }
+}
+
+fn fee_timing(env: &Env) -> FeeTiming {
+    env.storage()
+        .instance()
+        .get(&DataKey::FeeTiming)
+        .unwrap_or(FeeTiming::OnBuy)
+}
+
+// cobra `amount` de `from`: `fee_now` vai direto ao coletor e o resto fica no
+// contrato (escrow) ou segue para pay_out
+fn charge(
+    env: &Env,
+    token: &TokenClient,
+    from: &Address,
+    amount: i128,
+    fee_now: i128,
+    escrowed: bool,
+) {
+    if fee_now > 0 {
+        token.transfer(from, &fee_collector(env), &fee_now);
+    }
+    let rest = amount - fee_now;
+    let this = env.current_contract_address();
+    if !escrowed {
+        pay_out(env, token, from, rest);
+    } else if *from != this && rest > 0 {
+        token.transfer(from, &this, &rest);
+    }
+}
+
+// origem do pagamento em `place_order`: transfer do owner, transfer_from de um
+// allowance dado ao contrato, ou valor já recolhido ao contrato (prebuy_batch)
+#[derive(Clone, Copy)]
+enum Funding {
+    Transfer,
+    Allowance,
+    Prepaid,
+}
+
+// quanto do pagamento da ordem ainda está com o contrato/tesouraria (a taxa
+// cobrada na compra já saiu para o coletor)
+fn order_held(ord: &OrderRec) -> i128 {
+    if ord.fee_at_grant {
+        ord.price_paid
+    } else {
+        ord.price_paid - ord.fee
+    }
+}
+
+fn staked_of(env: &Env, owner: &Address) -> i128 {
+    env.storage()
+        .persistent()
+        .get(&DataKey::Staked(owner.clone()))
+        .unwrap_or(0)
+}
+
+// totais custodiados no token atual (stake, crédito), que travam o `set_token`
+fn custody_total(env: &Env, key: &DataKeyExt) -> i128 {
+    env.storage().instance().get(key).unwrap_or(0)
+}
+fn bump_custody(env: &Env, key: DataKeyExt, delta: i128) {
+    let total = custody_total(env, &key) + delta;
+    env.storage().instance().set(&key, &total);
+}
+
+// recusa trocar o token enquanto algum saldo ainda seria pago no token atual:
+// stake, crédito, sessão medida em aberto ou passe ainda reembolsável
+fn ensure_token_unused(env: &Env) {
+    let open_metered: u32 = env
+        .storage()
+        .instance()
+        .get(&DataKeyExt::OpenMetered)
+        .unwrap_or(0);
+    let passes_until: u64 = env
+        .storage()
+        .instance()
+        .get(&DataKeyExt::PassesLiveUntil)
+        .unwrap_or(0);
+    if custody_total(env, &DataKeyExt::TotalStaked) > 0
+        || custody_total(env, &DataKeyExt::TotalCredit) > 0
+        || open_metered > 0
+        || passes_until > env.ledger().timestamp()
+    {
+        panic_with_error!(env, Error::TokenInUse);
+    }
+}
+
+fn catalog_frozen(env: &Env) -> bool {
+    env.storage()
+        .instance()
+        .get(&DataKeyExt::CatalogFrozen)
+        .unwrap_or(false)
+}
+
+fn ensure_catalog_mutable(env: &Env) {
+    if catalog_frozen(env) {
+        panic_with_error!(env, Error::CatalogFrozen);
+    }
+}
+
+// desconto (bps) da maior faixa do pacote cujo min_qty cabe em `qty`
+fn tier_discount_bps(env: &Env, package_id: u32, qty: u32) -> u32 {
+    let tiers: Vec<(u32, u32)> = env
+        .storage()
+        .instance()
+        .get(&DataKeyExt::PriceTiers(package_id))
+        .unwrap_or(Vec::new(env));
+    let mut bps = 0;
+    for (min_qty, discount_bps) in tiers.iter() {
+        if qty >= min_qty {
+            bps = discount_bps;
+        }
+    }
+    bps
+}
+
+// preço efetivo para o owner: aplica StakeDiscountBps se o stake passar do limiar
+fn quote_price(env: &Env, owner: &Address, price: i128) -> i128 {
+    let bps: u32 = env
+        .storage()
+        .instance()
+        .get(&DataKey::StakeDiscountBps)
+        .unwrap_or(0);
+    if bps == 0 {
+        return price;
+    }
+    let threshold: i128 = env
+        .storage()
+        .instance()
+        .get(&DataKey::StakeThreshold)
+        .unwrap_or(0);
+    if staked_of(env, owner) > threshold {
+        price - price * bps as i128 / BPS_DENOMINATOR
+    } else {
+        price
+    }
+}
+
+fn credit_of(env: &Env, owner: &Address) -> i128 {
+    env.storage()
+        .persistent()
+        .get(&DataKey::Credit(owner.clone()))
+        .unwrap_or(0)
+}
+
+fn is_paused(env: &Env) -> bool {
+    env.storage()
+        .instance()
+        .get(&DataKey::Paused)
+        .unwrap_or(false)
+}
+
+fn is_blocked(env: &Env, owner: &Address) -> bool {
+    env.storage()
+        .persistent()
+        .get(&DataKeyExt::Blocked(owner.clone()))
+        .unwrap_or(false)
+}
+
+// guarda única das operações do owner (compra, grant, start/consumo):
+// contrato inicializado, não pausado e owner não bloqueado
+fn check_can_operate(env: &Env, owner: &Address) -> Result<(), Error> {
+    if !env.storage().instance().has(&DataKey::Admin) {
+        return Err(Error::NotInitialized);
+    }
+    if is_paused(env) {
+        return Err(Error::ContractPaused);
+    }
+    if is_blocked(env, owner) {
+        return Err(Error::OwnerBlocked);
+    }
+    Ok(())
+}
+
+fn ensure_can_operate(env: &Env, owner: &Address) {
+    if let Err(e) = check_can_operate(env, owner) {
+        panic_with_error!(env, e);
+    }
+}
+
+fn escrow_mode(env: &Env) -> bool {
+    env.storage()
+        .instance()
+        .get(&DataKey::EscrowMode)
+        .unwrap_or(false)
+}
+
+fn max_package_duration(env: &Env) -> u32 {
+    env.storage()
+        .instance()
+        .get(&DataKey::MaxPackageDurationSecs)
+        .unwrap_or(DEFAULT_MAX_PACKAGE_DURATION_SECS)
+}
+
+// modo de numeração global das ordens (GlobalOrderMode)
+fn global_order_mode(env: &Env) -> bool {
     env.storage()
+        .instance()
+        .get(&DataKeyExt::GlobalOrderMode)
+        .unwrap_or(false)
+}
+
+// storage helpers p/ OrderRec (persistent); no modo global a ordem fica em
+// GlobalOrder(id) junto com o owner e a leitura por (owner, id) só devolve
+// ordens daquele owner
+fn load_order(env: &Env, owner: &Address, order_id: u128) -> Option<OrderRec> {
+    if global_order_mode(env) {
+        return env
+            .storage()
+            .persistent()
+            .get::<_, (Address, OrderRec)>(&DataKeyExt::GlobalOrder(order_id))
+            .filter(|(o, _)| o == owner)
+            .map(|(_, rec)| rec);
+    }
+    let raw = env
+        .storage()
         .persistent()
-        .get::<_, OrderRec>(&DataKey::Order(owner.clone(), order_id))
+        .get::<_, Map<Symbol, Val>>(&DataKey::Order(owner.clone(), order_id))?;
+    if raw.contains_key(Symbol::new(env, "token")) {
+        return Some(
+            OrderRec::try_from_val(env, &raw.to_val())
+                .unwrap_or_else(|_| panic_with_error!(env, Error::SchemaMismatch)),
+        );
+    }
+    // ordem v1 (LegacyOrderRec): campos novos zerados e o token atual; sem valor
+    // nem data de compra gravados, o sweep de reembolso não a alcança
+    let v1 = LegacyOrderRec::try_from_val(env, &raw.to_val())
+        .unwrap_or_else(|_| panic_with_error!(env, Error::SchemaMismatch));
+    Some(OrderRec {
+        package_id: v1.package_id,
+        credited: v1.credited,
+        price_paid: 0,
+        escrowed: false,
+        refunded: false,
+        created_at: 0,
+        credited_secs: 0,
+        fee: 0,
+        fee_at_grant: false,
+        token: load_token(env),
+    })
 }
 fn save_order(env: &Env, owner: &Address, order_id: u128, rec: &OrderRec) {
+    if global_order_mode(env) {
+        env.storage().persistent().set(
+            &DataKeyExt::GlobalOrder(order_id),
+            &(owner.clone(), rec.clone()),
+        );
+        return;
+    }
     env.storage()
         .persistent()
         .set(&DataKey::Order(owner.clone(), order_id), rec);
 }
 
-// helper p/ contador determinístico (instance): NextOrder(owner) -> u128
+// estado da ordem derivado dos flags refunded/credited
+fn order_status(ord: &OrderRec) -> OrderStatus {
+    if ord.refunded {
+        OrderStatus::Refunded
+    } else if ord.credited {
+        OrderStatus::Credited
+    } else {
+        OrderStatus::Pending
+    }
+}
+
+// último order_id emitido para o owner (0 = nenhum)
+fn last_order_id(env: &Env, owner: &Address) -> u128 {
+    if global_order_mode(env) {
+        return env
+            .storage()
+            .instance()
+            .get(&DataKeyExt::NextGlobalOrder)
+            .unwrap_or(0);
+    }
+    env.storage()
+        .instance()
+        .get(&DataKey::NextOrder(owner.clone()))
+        .unwrap_or(0)
+}
+
+// helper p/ contador determinístico (instance): NextOrder(owner) -> u128; no
+// modo global o id vem de NextGlobalOrder e NextOrder(owner) só conta as ordens
 fn next_order_id(env: &Env, owner: &Address) -> u128 {
     let key = DataKey::NextOrder(owner.clone());
     let current: u128 = env.storage().instance().get(&key).unwrap_or(0);
     let next = current + 1;
     env.storage().instance().set(&key, &next);
+    if global_order_mode(env) {
+        let inst = env.storage().instance();
+        let id: u128 = inst.get(&DataKeyExt::NextGlobalOrder).unwrap_or(0) + 1;
+        inst.set(&DataKeyExt::NextGlobalOrder, &id);
+        return id;
+    }
     next
 }
 
+fn open_orders_of(env: &Env, owner: &Address) -> u32 {
+    env.storage()
+        .persistent()
+        .get(&DataKey::OpenOrders(owner.clone()))
+        .unwrap_or(0)
+}
+
+fn open_order_value_of(env: &Env) -> i128 {
+    env.storage()
+        .instance()
+        .get(&DataKeyExt::OpenOrderValue)
+        .unwrap_or(0)
+}
+
+// ajusta o contador de ordens pendentes do owner (+1 na compra, -1 ao fechar)
+// e o total global ainda reembolsável (`held` da ordem)
+fn bump_open_orders(env: &Env, owner: &Address, opened: bool, held: i128) {
+    let total = open_order_value_of(env);
+    let total = if opened { total + held } else { total - held };
+    env.storage()
+        .instance()
+        .set(&DataKeyExt::OpenOrderValue, &total);
+    let n = open_orders_of(env, owner);
+    let n = if opened {
+        n.saturating_add(1)
+    } else {
+        n.saturating_sub(1)
+    };
+    env.storage()
+        .persistent()
+        .set(&DataKey::OpenOrders(owner.clone()), &n);
+}
+
+fn max_open_orders(env: &Env) -> u32 {
+    env.storage()
+        .instance()
+        .get(&DataKey::MaxOpenOrders)
+        .unwrap_or(0)
+}
+
+fn validate_package(env: &Env, duration_secs: u32) {
+    if duration_secs > max_package_duration(env) {
+        panic_with_error!(env, Error::InvalidPackage);
+    }
+}
+
+fn store_package(env: &Env, id: u32, price: i128, duration_secs: u32) {
+    let pkg = Package {
+        price,
+        duration_secs,
+    };
+    env.storage().instance().set(&DataKey::Package(id), &pkg);
+    env.storage()
+        .instance()
+        .set(&DataKeyExt::PackageUpdatedAt(id), &env.ledger().timestamp());
+    let mut ids = package_ids(env);
+    if !ids.contains(id) {
+        ids.push_back(id);
+        env.storage().instance().set(&DataKeyExt::PackageIds, &ids);
+    }
+    publish(env, (symbol_short!("pkg_set"), id), (price, duration_secs));
+}
+
+// ids do catálogo na ordem de cadastro (mantido por store_package/delete_package)
+fn package_ids(env: &Env) -> Vec<u32> {
+    env.storage()
+        .instance()
+        .get(&DataKeyExt::PackageIds)
+        .unwrap_or_else(|| Vec::new(env))
+}
+
+// todas as condições que impedem um grant, em um só lugar (usado por grant e can_grant)
+fn check_grantable(
+    env: &Env,
+    owner: &Address,
+    order_id: u128,
+) -> Result<(OrderRec, Package), Error> {
+    check_can_operate(env, owner)?;
+    let ord = load_order(env, owner, order_id).ok_or(Error::OrderNotFound)?;
+    if ord.credited {
+        return Err(Error::AlreadyGranted);
+    }
+    if ord.refunded {
+        return Err(Error::InvalidOrderState);
+    }
+    let delay: u64 = env
+        .storage()
+        .instance()
+        .get(&DataKeyExt::GrantDelaySecs)
+        .unwrap_or(0);
+    if env.ledger().timestamp().saturating_sub(ord.created_at) < delay {
+        return Err(Error::GrantTooEarly);
+    }
+    let pkg: Package = env
+        .storage()
+        .instance()
+        .get(&DataKey::Package(ord.package_id))
+        .ok_or(Error::PackageNotFound)?;
+    // StrictGrant: pacote editado depois da compra não é creditado
+    let strict: bool = env
+        .storage()
+        .instance()
+        .get(&DataKeyExt::StrictGrant)
+        .unwrap_or(false);
+    if strict {
+        let updated_at: u64 = env
+            .storage()
+            .instance()
+            .get(&DataKeyExt::PackageUpdatedAt(ord.package_id))
+            .unwrap_or(0);
+        if updated_at > ord.created_at {
+            return Err(Error::PackageChanged);
+        }
+    }
+    Ok((ord, pkg))
+}
+
+fn sale_rules(env: &Env, package_id: u32) -> Option<SaleRules> {
+    env.storage()
+        .instance()
+        .get(&DataKey::PackageSale(package_id))
+}
+
+// pacote habilitado, dentro da janela de venda e com estoque
+fn check_on_sale(env: &Env, package_id: u32) -> Result<(), Error> {
+    let Some(rules) = sale_rules(env, package_id) else {
+        return Ok(());
+    };
+    let now = env.ledger().timestamp();
+    let in_window = now >= rules.starts_at && (rules.ends_at == 0 || now < rules.ends_at);
+    if !rules.enabled || !in_window || rules.stock == Some(0) {
+        return Err(Error::PackageUnavailable);
+    }
+    Ok(())
+}
+
+// compra agora cai numa janela de venda configurada (starts_at/ends_at não nulos)
+fn in_sale_window(env: &Env, package_id: u32) -> bool {
+    let Some(rules) = sale_rules(env, package_id) else {
+        return false;
+    };
+    let now = env.ledger().timestamp();
+    (rules.starts_at > 0 || rules.ends_at > 0)
+        && now >= rules.starts_at
+        && (rules.ends_at == 0 || now < rules.ends_at)
+}
+
+// baixa uma unidade do estoque (se limitado)
+fn consume_stock(env: &Env, package_id: u32) {
+    if let Some(mut rules) = sale_rules(env, package_id) {
+        if let Some(stock) = rules.stock {
+            rules.stock = Some(stock.saturating_sub(1));
+            env.storage()
+                .instance()
+                .set(&DataKey::PackageSale(package_id), &rules);
+        }
+    }
+}
+
+// estatística por pacote (instance): PackageGrantedSecs(id) -> (vezes, segundos)
+fn package_stats_of(env: &Env, package_id: u32) -> (u32, u64) {
+    env.storage()
+        .instance()
+        .get(&DataKey::PackageGrantedSecs(package_id))
+        .unwrap_or((0, 0))
+}
+// `closed`: a ordem terminou de ser creditada (conta uma vez por ordem)
+fn record_package_grant(env: &Env, package_id: u32, secs: u64, closed: bool) {
+    let (times, total) = package_stats_of(env, package_id);
+    env.storage().instance().set(
+        &DataKey::PackageGrantedSecs(package_id),
+        &(
+            times.saturating_add(closed as u32),
+            total.saturating_add(secs),
+        ),
+    );
+}
+
+// total agregado de segundos creditados por origem (instance)
+fn granted_secs_of(env: &Env, source: GrantSource) -> u64 {
+    env.storage()
+        .instance()
+        .get(&DataKey::GrantedSecs(source))
+        .unwrap_or(0)
+}
+fn record_granted(env: &Env, source: GrantSource, secs: u64) {
+    let total = granted_secs_of(env, source).saturating_add(secs);
+    env.storage()
+        .instance()
+        .set(&DataKey::GrantedSecs(source), &total);
+}
+
+// crédito de cortesia (sem ordem): conta como Comp e emite `comp`
+fn comp_credit(env: &Env, owner: &Address, secs: u64) -> (u64, Session) {
+    let (s, credited) = credit_session(env, owner, secs);
+    record_granted(env, GrantSource::Comp, credited);
+    publish(
+        env,
+        (symbol_short!("comp"), owner.clone()),
+        (credited, s.remaining_secs),
+    );
+    (credited, s)
+}
+
 // -------------------------------------------------------------
 // HELPERS (tempo)
 // -------------------------------------------------------------
-fn remaining_at(_env: &Env, s: &Session, now: u64) -> u64 {
+// folga global vigente (GlobalGraceOffset): (segundos, ts em que foi definida)
+fn grace_config(env: &Env) -> (u64, u64) {
+    env.storage()
+        .instance()
+        .get(&DataKeyExt::GlobalGraceOffset)
+        .unwrap_or((0, 0))
+}
+
+// folga que vale para a sessão: só a execução que já rodava quando a folga foi
+// definida a recebe; ao consolidar ela entra no saldo e o rebase de
+// `started_at` encerra o direito (não se acumula com pause/start)
+fn grace_for(env: &Env, s: &Session) -> u64 {
+    let (secs, set_at) = grace_config(env);
+    if s.started_at > 0 && s.started_at < set_at {
+        secs
+    } else {
+        0
+    }
+}
+
+fn remaining_at(env: &Env, s: &Session, now: u64) -> u64 {
     if s.started_at == 0 {
         s.remaining_secs
     } else {
         s.remaining_secs
+            .saturating_add(grace_for(env, s))
             .saturating_sub(now.saturating_sub(s.started_at))
     }
 }
+// arredonda o consumo para cima ao múltiplo de ConsumptionRoundSecs (0/1 = exato)
+fn round_consumed(env: &Env, elapsed: u64) -> u64 {
+    let step: u64 = env
+        .storage()
+        .instance()
+        .get(&DataKeyExt::ConsumptionRoundSecs)
+        .unwrap_or(0);
+    if step <= 1 {
+        return elapsed;
+    }
+    elapsed.div_ceil(step).saturating_mul(step)
+}
+
+// consolida o tempo já consumido de uma sessão ativa: `remaining_secs` passa a
+// valer o saldo ao vivo em `now` (folga global incluída) e `started_at` é
+// rebaseado para `now` (ou zerado se o saldo acabou), sem alterar o fim efetivo
+// da sessão. Quem pausa depois só precisa zerar `started_at`; o arredondamento
+// de ConsumptionRoundSecs só entra quando a execução termina (`save_session_at`).
+fn settle_at(env: &Env, s: &mut Session, now: u64) {
+    if s.started_at > 0 {
+        let used = now.saturating_sub(s.started_at);
+        s.remaining_secs = s
+            .remaining_secs
+            .saturating_add(grace_for(env, s))
+            .saturating_sub(used);
+        s.started_at = if s.remaining_secs > 0 { now } else { 0 };
+    }
+}
+
+// credita `secs` na sessão do owner (consolidando antes, se estiver rodando),
+// respeitando MaxSessionSecs; devolve a sessão e os segundos de fato creditados
+fn credit_session(env: &Env, owner: &Address, secs: u64) -> (Session, u64) {
+    credit_session_at(
+        env,
+        &DataKey::Session(owner.clone()),
+        owner,
+        Some(owner),
+        secs,
+    )
+}
+
+// `credit_session` na sessão gravada em `key` (individual ou de grupo). Com a
+// sessão rodando, consolida e só então soma: o fim efetivo avança exatamente
+// os segundos creditados, sem perda pelo rebase de `started_at`. `consumer`
+// recebe no ConsumedSecs o tempo consolidado (None em sessão de grupo).
+fn credit_session_at<K: IntoVal<Env, Val>>(
+    env: &Env,
+    key: &K,
+    owner: &Address,
+    consumer: Option<&Address>,
+    secs: u64,
+) -> (Session, u64) {
+    let mut s = load_session_at(env, key);
+    settle_at(env, &mut s, env.ledger().timestamp());
+
+    // numa execução limitada pelo teto diário o crédito vai para o saldo retido
+    let held = if s.started_at > 0 {
+        cap_hold(env, key)
+    } else {
+        None
+    };
+    let balance = s.remaining_secs.saturating_add(held.unwrap_or(0));
+    let mut credited = secs;
+    let cap: u64 = env
+        .storage()
+        .instance()
+        .get(&DataKey::MaxSessionSecs)
+        .unwrap_or(0);
+    if cap > 0 && balance.saturating_add(secs) > cap {
+        credited = cap.saturating_sub(balance);
+        // no modo rejeição o evento só aparece nos diagnostics da simulação
+        publish(
+            env,
+            (symbol_short!("cap_hit"), owner.clone()),
+            (secs, credited),
+        );
+        let clamp: bool = env
+            .storage()
+            .instance()
+            .get(&DataKey::CapClamp)
+            .unwrap_or(false);
+        if !clamp {
+            panic_with_error!(env, Error::SessionCapExceeded);
+        }
+    }
+
+    match held {
+        Some(h) => env
+            .storage()
+            .persistent()
+            .set(&cap_hold_key(env, key), &h.saturating_add(credited)),
+        None => s.remaining_secs = s.remaining_secs.saturating_add(credited),
+    }
+    save_session_at(env, key, consumer, &s);
+    (s, credited)
+}
+
+// auditoria de pausas: abre o intervalo no pause e fecha no próximo start
+fn open_pause_gap(env: &Env, owner: &Address, now: u64) {
+    env.storage()
+        .persistent()
+        .set(&DataKey::PausedAt(owner.clone()), &now);
+}
+fn close_pause_gap(env: &Env, owner: &Address, now: u64) {
+    let key = DataKey::PausedAt(owner.clone());
+    if let Some(paused_at) = env.storage().persistent().get::<_, u64>(&key) {
+        let total_key = DataKey::TotalPausedSecs(owner.clone());
+        let total: u64 = env.storage().persistent().get(&total_key).unwrap_or(0);
+        env.storage().persistent().set(
+            &total_key,
+            &total.saturating_add(now.saturating_sub(paused_at)),
+        );
+        env.storage().persistent().remove(&key);
+    }
+}
+
 fn load_session(env: &Env, owner: &Address) -> Session {
+    load_session_at(env, &DataKey::Session(owner.clone()))
+}
+
+fn load_session_at<K: IntoVal<Env, Val>>(env: &Env, key: &K) -> Session {
     env.storage()
         .persistent()
-        .get::<_, Session>(&DataKey::Session(owner.clone()))
+        .get::<_, Session>(key)
         .unwrap_or(Session {
             remaining_secs: 0,
             started_at: 0,
+            not_before: 0,
+            last_activity: 0,
         })
 }
 
-fn save_session(env: &Env, owner: &Address, s: &Session) {
-    env.storage()
-        .persistent()
-        .set(&DataKey::Session(owner.clone()), s);
-}
+fn save_session(env: &Env, owner: &Address, s: &Session) {
+    save_session_at(env, &DataKey::Session(owner.clone()), Some(owner), s);
+}
+
+// grava a sessão (carimbando `last_activity`) e ajusta o ActiveCount na
+// transição pausada <-> rodando. Se a execução anterior foi consolidada
+// (`started_at` mudou), soma o tempo usado nela ao ConsumedSecs do `consumer`;
+// como toda consolidação rebaseia `started_at`, nada é contado duas vezes.
+fn save_session_at<K: IntoVal<Env, Val>>(
+    env: &Env,
+    key: &K,
+    consumer: Option<&Address>,
+    s: &Session,
+) {
+    let now = env.ledger().timestamp();
+    let mut s = s.clone();
+    s.last_activity = now;
+    let old = load_session_at(env, key);
+    let run_key = DataKeyExt::RunStartedAt(session_tag(env, key));
+    // execução terminando: debita uma vez só o arredondamento do tempo exato
+    // desde o início dela (os rebases no meio não arredondam)
+    let mut rounding = 0;
+    if old.started_at > 0 && s.started_at == 0 {
+        if let Some(run_start) = env.storage().persistent().get::<_, u64>(&run_key) {
+            let ran = now.saturating_sub(run_start);
+            rounding = round_consumed(env, ran)
+                .saturating_sub(ran)
+                .min(s.remaining_secs);
+            s.remaining_secs -= rounding;
+        }
+    }
+    if let Some(owner) = consumer {
+        if old.started_at > 0 && s.started_at != old.started_at {
+            let used = now
+                .saturating_sub(old.started_at)
+                .min(old.remaining_secs)
+                .saturating_add(rounding);
+            let ckey = DataKeyExt::ConsumedSecs(owner.clone());
+            let total: u64 = env.storage().persistent().get(&ckey).unwrap_or(0);
+            env.storage()
+                .persistent()
+                .set(&ckey, &total.saturating_add(used));
+            record_daily_usage(env, owner, old.started_at, used, now);
+        }
+        // o teto também vale na consolidação: atingido, a execução para aqui
+        let cap = daily_cap_secs(env);
+        if cap > 0 && s.started_at > 0 && daily_usage_of(env, owner, now) >= cap {
+            s.started_at = 0;
+        }
+    }
+    // parada a execução, o saldo retido pelo teto diário volta para a sessão
+    if s.started_at == 0 {
+        if let Some(held) = cap_hold(env, key) {
+            s.remaining_secs = s.remaining_secs.saturating_add(held);
+            env.storage().persistent().remove(&cap_hold_key(env, key));
+        }
+    }
+    let was_running = old.started_at > 0;
+    let running = s.started_at > 0;
+    if running && !was_running {
+        env.storage().persistent().set(&run_key, &now);
+    } else if was_running && !running {
+        env.storage().persistent().remove(&run_key);
+    }
+    if was_running != running {
+        let count = active_count_of(env);
+        let count = if running {
+            count.saturating_add(1)
+        } else {
+            count.saturating_sub(1)
+        };
+        env.storage().instance().set(&DataKey::ActiveCount, &count);
+    }
+    env.storage().persistent().set(key, &s);
+}
+
+fn daily_cap_secs(env: &Env) -> u64 {
+    env.storage()
+        .instance()
+        .get(&DataKeyExt::DailyCapSecs)
+        .unwrap_or(0)
+}
+
+// segundos consumidos pelo owner no dia UTC de `now` (zera na virada do dia)
+fn daily_usage_of(env: &Env, owner: &Address, now: u64) -> u64 {
+    let (day, secs): (u64, u64) = env
+        .storage()
+        .persistent()
+        .get(&DataKeyExt::DailyUsage(owner.clone()))
+        .unwrap_or((0, 0));
+    if day == now / SECS_PER_DAY {
+        secs
+    } else {
+        0
+    }
+}
+
+// soma ao dia corrente só a parte da execução `[started_at, started_at + used)`
+// que caiu depois da meia-noite UTC; só registra com o teto ligado
+fn record_daily_usage(env: &Env, owner: &Address, started_at: u64, used: u64, now: u64) {
+    if daily_cap_secs(env) == 0 {
+        return;
+    }
+    let day = now / SECS_PER_DAY;
+    let from = started_at.max(day * SECS_PER_DAY);
+    let today = started_at.saturating_add(used).saturating_sub(from);
+    let total = daily_usage_of(env, owner, now).saturating_add(today);
+    env.storage()
+        .persistent()
+        .set(&DataKeyExt::DailyUsage(owner.clone()), &(day, total));
+}
+
+// identifica a sessão gravada em `key` (individual, nomeada ou de grupo) nas
+// chaves auxiliares por execução: sha256 do XDR da chave
+fn session_tag<K: IntoVal<Env, Val>>(env: &Env, key: &K) -> BytesN<32> {
+    let key: Val = key.into_val(env);
+    env.crypto().sha256(&key.to_xdr(env)).into()
+}
+
+// saldo retido (CapHeld) da execução em `key`; None = execução sem teto diário
+fn cap_hold_key<K: IntoVal<Env, Val>>(env: &Env, key: &K) -> DataKeyExt {
+    DataKeyExt::CapHeld(session_tag(env, key))
+}
+fn cap_hold<K: IntoVal<Env, Val>>(env: &Env, key: &K) -> Option<u64> {
+    env.storage().persistent().get(&cap_hold_key(env, key))
+}
+// remove a retenção de `key` e devolve o valor retido, para quem zera ou move o
+// saldo inteiro não deixar o `save_session_at` devolvê-lo à sessão
+fn take_cap_hold<K: IntoVal<Env, Val>>(env: &Env, key: &K) -> u64 {
+    let held = cap_hold(env, key).unwrap_or(0);
+    env.storage().persistent().remove(&cap_hold_key(env, key));
+    held
+}
+
+// início da execução em andamento na sessão de `key` (None = parada)
+fn run_started_at<K: IntoVal<Env, Val>>(env: &Env, key: &K) -> Option<u64> {
+    env.storage()
+        .persistent()
+        .get(&DataKeyExt::RunStartedAt(session_tag(env, key)))
+}
+
+// start com o teto diário (DailyCapSecs) ligado: recusa com o teto já atingido
+// e deixa a execução rodar só o que resta dele hoje, retendo o excedente do
+// saldo até a sessão parar (`save_session_at` devolve)
+fn clamp_to_daily_cap<K: IntoVal<Env, Val>>(
+    env: &Env,
+    key: &K,
+    owner: &Address,
+    s: &mut Session,
+    now: u64,
+) {
+    let cap = daily_cap_secs(env);
+    if cap == 0 {
+        return;
+    }
+    let left = cap.saturating_sub(daily_usage_of(env, owner, now));
+    if left == 0 {
+        panic_with_error!(env, Error::DailyCapReached);
+    }
+    let held = s.remaining_secs.saturating_sub(left);
+    s.remaining_secs -= held;
+    env.storage()
+        .persistent()
+        .set(&cap_hold_key(env, key), &held);
+}
+
+// execução que já esgotou o que o teto deixou rodar: consolida e para, o que
+// devolve o saldo retido antes de um novo start
+fn stop_capped_run<K: IntoVal<Env, Val>>(
+    env: &Env,
+    key: &K,
+    owner: &Address,
+    s: &mut Session,
+    now: u64,
+) {
+    if s.started_at > 0 && remaining_at(env, s, now) == 0 && cap_hold(env, key).is_some() {
+        settle_at(env, s, now);
+        s.started_at = 0;
+        save_session_at(env, key, Some(owner), s);
+        *s = load_session_at(env, key);
+    }
+}
+
+// sessão pausada com saldo e parada há mais que MaxIdleSecs (0 = nunca expira)
+fn idle_expired(env: &Env, s: &Session, now: u64) -> bool {
+    let max: u64 = env
+        .storage()
+        .instance()
+        .get(&DataKeyExt::MaxIdleSecs)
+        .unwrap_or(0);
+    max > 0
+        && s.started_at == 0
+        && s.remaining_secs > 0
+        && now.saturating_sub(s.last_activity) > max
+}
+
+fn active_count_of(env: &Env) -> u32 {
+    env.storage()
+        .instance()
+        .get(&DataKey::ActiveCount)
+        .unwrap_or(0)
+}
+
+// -------------------------------------------------------------
+// HELPERS (grant / start)
+// -------------------------------------------------------------
+
+// autoriza: caller deve ser admin OU o próprio owner
+fn authorize_grant(env: &Env, caller: &Address, owner: &Address) {
+    let by_admin = *caller == load_admin(env) && admin_grant_allowed(env);
+    let by_owner = caller == owner && self_grant_allowed(env);
+    if !by_admin && !by_owner {
+        panic_with_error!(env, Error::Unauthorized);
+    }
+    caller.require_auth();
+}
+
+fn self_grant_allowed(env: &Env) -> bool {
+    env.storage()
+        .instance()
+        .get(&DataKeyExt::SelfGrantAllowed)
+        .unwrap_or(true)
+}
+
+fn admin_grant_allowed(env: &Env) -> bool {
+    env.storage()
+        .instance()
+        .get(&DataKey::AdminGrantAllowed)
+        .unwrap_or(true)
+}
+
+// start/pause: o próprio owner ou o delegado registrado
+fn authorize_session(env: &Env, caller: &Address, owner: &Address) {
+    if caller != owner {
+        let delegate: Option<Address> = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Delegate(owner.clone()));
+        if delegate.as_ref() != Some(caller) {
+            panic_with_error!(env, Error::Unauthorized);
+        }
+    }
+    caller.require_auth();
+}
+
+// rejeita novo grant do mesmo pacote dentro do cooldown e registra o atual
+fn check_grant_cooldown(env: &Env, owner: &Address, package_id: u32) {
+    let cooldown: u64 = env
+        .storage()
+        .instance()
+        .get(&DataKeyExt::PackageCooldownSecs(package_id))
+        .unwrap_or(0);
+    if cooldown == 0 {
+        return;
+    }
+    let now = env.ledger().timestamp();
+    let key = DataKeyExt::LastGrant(owner.clone(), package_id);
+    if let Some(last) = env.storage().persistent().get::<_, u64>(&key) {
+        if now < last.saturating_add(cooldown) {
+            panic_with_error!(env, Error::GrantCooldown);
+        }
+    }
+    env.storage().persistent().set(&key, &now);
+}
+
+// credita o saldo restante da ordem na sessão do owner; devolve a sessão atualizada
+fn apply_grant(env: &Env, owner: &Address, order_id: u128) -> Session {
+    credit_order(env, owner, order_id, None)
+}
+
+// credita `secs` da ordem (ou tudo o que falta, se `None`); a ordem só é marcada
+// como creditada quando o pacote inteiro tiver sido entregue
+fn credit_order(env: &Env, owner: &Address, order_id: u128, secs: Option<u64>) -> Session {
+    credit_order_into(
+        env,
+        owner,
+        order_id,
+        secs,
+        &DataKey::Session(owner.clone()),
+        Some(owner),
+    )
+}
+
+// `credit_order` creditando na sessão gravada em `into` (individual ou de grupo)
+fn credit_order_into<K: IntoVal<Env, Val>>(
+    env: &Env,
+    owner: &Address,
+    order_id: u128,
+    secs: Option<u64>,
+    into: &K,
+    consumer: Option<&Address>,
+) -> Session {
+    // ordem precisa existir, estar pendente e apontar para um pacote válido
+    let (mut ord, pkg) =
+        check_grantable(env, owner, order_id).unwrap_or_else(|e| panic_with_error!(env, e));
+    let left = (pkg.duration_secs as u64).saturating_sub(ord.credited_secs);
+    let secs = secs.unwrap_or(left);
+    if secs == 0 || secs > left {
+        panic_with_error!(env, Error::InvalidOrderState);
+    }
+    // cooldown conta a partir do primeiro crédito de cada ordem
+    if ord.credited_secs == 0 {
+        check_grant_cooldown(env, owner, ord.package_id);
+    }
+    ord.credited_secs += secs;
+    ord.credited = ord.credited_secs == pkg.duration_secs as u64;
+
+    // credita tempo (não inicia; se já estiver rodando, segue rodando)
+    let (s, credited) = credit_session_at(env, into, owner, consumer, secs);
+    record_package_grant(env, ord.package_id, credited, ord.credited);
+    record_granted(env, GrantSource::Paid, credited);
+
+    // escrow: libera o pagamento retido para a tesouraria ao fechar a ordem,
+    // separando antes a taxa quando ela é cobrada no grant
+    if ord.credited && ord.escrowed {
+        let token = TokenClient::new(env, &ord.token);
+        let this = env.current_contract_address();
+        let mut held = order_held(&ord);
+        if ord.fee_at_grant && ord.fee > 0 {
+            token.transfer(&this, &fee_collector(env), &ord.fee);
+            held -= ord.fee;
+        }
+        pay_out(env, &token, &this, held);
+    }
+
+    if ord.credited {
+        bump_open_orders(env, owner, false, order_held(&ord));
+        let key = DataKey::CreditedCount(owner.clone());
+        let n: u32 = env.storage().persistent().get(&key).unwrap_or(0);
+        env.storage().persistent().set(&key, &(n + 1));
+    }
+    save_order(env, owner, order_id, &ord);
+    emit_grant(env, owner, order_id, s.remaining_secs);
+    s
+}
+
+// a expiração é preguiçosa: a primeira leitura que encontra a sessão rodando
+// com saldo zerado emite `("expired", owner)`; o marcador (started_at da
+// execução) garante um único evento por expiração
+fn observe_expiry(env: &Env, owner: &Address, s: &Session, now: u64) {
+    observe_expiry_at(
+        env,
+        DataKeyExt::ExpiryNotified(owner.clone()),
+        (symbol_short!("expired"), owner.clone()),
+        s,
+        now,
+    );
+}
+
+// `observe_expiry` do slot nomeado: evento `("s_expired", owner, name)`
+fn observe_named_expiry(env: &Env, owner: &Address, name: &Symbol, s: &Session, now: u64) {
+    observe_expiry_at(
+        env,
+        DataKeyExt::NamedExpiryNotified(owner.clone(), name.clone()),
+        (symbol_short!("s_expired"), owner.clone(), name.clone()),
+        s,
+        now,
+    );
+}
+
+fn observe_expiry_at<T: IntoVal<Env, Vec<Val>>>(
+    env: &Env,
+    key: DataKeyExt,
+    topics: T,
+    s: &Session,
+    now: u64,
+) {
+    if s.started_at == 0 || remaining_at(env, s, now) > 0 {
+        return;
+    }
+    if env.storage().persistent().get::<_, u64>(&key) == Some(s.started_at) {
+        return;
+    }
+    env.storage().persistent().set(&key, &s.started_at);
+    publish(env, topics, ());
+}
+
+// checagens de todo start de sessão pausada (principal ou nomeada): agendamento
+// (`not_before`), expiração por inatividade, MinStartSecs e teto diário
+fn ensure_startable<K: IntoVal<Env, Val>>(
+    env: &Env,
+    key: &K,
+    owner: &Address,
+    s: &mut Session,
+    now: u64,
+) {
+    if now < s.not_before {
+        panic_with_error!(env, Error::NotYetAvailable);
+    }
+    if idle_expired(env, s, now) {
+        panic_with_error!(env, Error::SessionIdleExpired);
+    }
+    let min: u64 = env
+        .storage()
+        .instance()
+        .get(&DataKey::MinStartSecs)
+        .unwrap_or(0);
+    if s.remaining_secs < min {
+        panic_with_error!(env, Error::BelowMinStart);
+    }
+    clamp_to_daily_cap(env, key, owner, s, now);
+}
+
+// com AutoSettle ligado, encerra no storage a sessão rodando que já expirou
+// (saldo e started_at zerados, evento `expired`); desligado, não escreve nada
+fn auto_settle(env: &Env, owner: &Address, now: u64) {
+    let on: bool = env
+        .storage()
+        .instance()
+        .get(&DataKeyExt::AutoSettle)
+        .unwrap_or(false);
+    if !on {
+        return;
+    }
+    let mut s = load_session(env, owner);
+    if s.started_at == 0 || remaining_at(env, &s, now) > 0 {
+        return;
+    }
+    observe_expiry(env, owner, &s, now);
+    s.remaining_secs = 0;
+    s.started_at = 0;
+    save_session(env, owner, &s);
+}
+
+// inicia a sessão (sem checar auth); no-op se já rodando ou sem saldo
+fn start_session(env: &Env, owner: &Address) {
+    ensure_can_operate(env, owner);
+    let now = env.ledger().timestamp();
+    let key = DataKey::Session(owner.clone());
+    let mut s = load_session(env, owner);
+    observe_expiry(env, owner, &s, now);
+    stop_capped_run(env, &key, owner, &mut s, now);
+    if remaining_at(env, &s, now) == 0 {
+        return;
+    }
+    if s.started_at == 0 {
+        ensure_startable(env, &key, owner, &mut s, now);
+        s.started_at = now;
+        save_session(env, owner, &s);
+        close_pause_gap(env, owner, now);
+        // corpo: (started_at, projected_end) p/ agendar aviso de fim sem nova leitura
+        publish(
+            env,
+            (symbol_short!("start"), owner.clone()),
+            (now, now.saturating_add(s.remaining_secs)),
+        );
+    }
+}
+
+// pausa a sessão pré-paga (sem checar auth), congelando o saldo
+fn pause_session(env: &Env, owner: &Address) {
+    let now = env.ledger().timestamp();
+    let mut s = load_session(env, owner);
+    observe_expiry(env, owner, &s, now);
+    if s.started_at > 0 {
+        // com saldo ainda correndo, mede desde o início da execução
+        // (RunStartedAt; o settle/crédito rebaseia `started_at`)
+        if remaining_at(env, &s, now) > 0 {
+            let last_start =
+                run_started_at(env, &DataKey::Session(owner.clone())).unwrap_or(s.started_at);
+            ensure_min_active(env, last_start, now);
+        }
+        settle_at(env, &mut s, now);
+        s.started_at = 0;
+        save_session(env, owner, &s);
+        open_pause_gap(env, owner, now);
+        publish(
+            env,
+            (symbol_short!("pause"), owner.clone()),
+            s.remaining_secs,
+        );
+    }
+}
+
+// anti-flicker: recusa pausar antes de MinActiveSecs desde `started` (0 = off)
+fn ensure_min_active(env: &Env, started: u64, now: u64) {
+    let min: u64 = env
+        .storage()
+        .instance()
+        .get(&DataKeyExt::MinActiveSecs)
+        .unwrap_or(0);
+    if min > 0 && now.saturating_sub(started) < min {
+        panic_with_error!(env, Error::TooSoonToPause);
+    }
+}
+
+fn metered_started_at(env: &Env, owner: &Address) -> Option<u64> {
+    env.storage()
+        .persistent()
+        .get(&DataKey::MeteredStart(owner.clone()))
+}
+
+// fim projetado da sessão medida em aberto: até onde o allowance (e o saldo)
+// do owner cobre `PerSecondPrice` desde o start
+fn metered_ends_at(env: &Env, owner: &Address, started: u64) -> u64 {
+    let price = per_second_price(env);
+    if price <= 0 {
+        return started;
+    }
+    let token = TokenClient::new(env, &load_token(env));
+    let funds = token
+        .allowance(owner, &env.current_contract_address())
+        .min(token.balance(owner));
+    started.saturating_add((funds.max(0) / price) as u64)
+}
+
+// fim do acesso em andamento: sessão pré-paga rodando (com a folga global) ou,
+// sem ela, a sessão medida em aberto; None com as duas paradas
+fn access_ends_at(env: &Env, owner: &Address) -> Option<u64> {
+    let s = load_session(env, owner);
+    if s.started_at > 0 {
+        return Some(
+            s.started_at
+                .saturating_add(s.remaining_secs)
+                .saturating_add(grace_for(env, &s)),
+        );
+    }
+    metered_started_at(env, owner).map(|started| metered_ends_at(env, owner, started))
+}
+
+fn per_second_price(env: &Env) -> i128 {
+    env.storage()
+        .instance()
+        .get(&DataKey::PerSecondPrice)
+        .unwrap_or(0)
+}
+
+// encerra a sessão medida em aberto (se houver) cobrando elapsed * PerSecondPrice
+// via allowance: owner -> contrato -> tesouraria/splits
+fn settle_metered(env: &Env, owner: &Address) {
+    let key = DataKey::MeteredStart(owner.clone());
+    let Some(started) = env.storage().persistent().get::<_, u64>(&key) else {
+        return;
+    };
+    env.storage().persistent().remove(&key);
+    let open: u32 = env
+        .storage()
+        .instance()
+        .get(&DataKeyExt::OpenMetered)
+        .unwrap_or(0);
+    env.storage()
+        .instance()
+        .set(&DataKeyExt::OpenMetered, &open.saturating_sub(1));
+    env.storage().instance().set(
+        &DataKey::ActiveCount,
+        &active_count_of(env).saturating_sub(1),
+    );
+
+    let elapsed = env.ledger().timestamp().saturating_sub(started);
+    let charge = elapsed as i128 * per_second_price(env);
+    if charge > 0 {
+        let token = TokenClient::new(env, &load_token(env));
+        let this = env.current_contract_address();
+        token.transfer_from(&this, owner, &this, &charge);
+        pay_out(env, &token, &this, charge);
+    }
+    publish(
+        env,
+        (symbol_short!("m_charge"), owner.clone()),
+        (elapsed, charge),
+    );
+}
+
+fn load_time_pass(env: &Env, pass_id: u128) -> TimePass {
+    env.storage()
+        .persistent()
+        .get(&DataKeyExt::TimePass(pass_id))
+        .unwrap_or_else(|| panic_with_error!(env, Error::PassNotFound))
+}
+
+// grupo precisa existir; devolve o dono
+fn group_owner(env: &Env, group: &Symbol) -> Address {
+    env.storage()
+        .persistent()
+        .get(&DataKey::GroupOwner(group.clone()))
+        .unwrap_or_else(|| panic_with_error!(env, Error::GroupNotFound))
+}
+
+fn is_group_member(env: &Env, group: &Symbol, addr: &Address) -> bool {
+    env.storage()
+        .persistent()
+        .get(&DataKey::GroupMember(group.clone(), addr.clone()))
+        .unwrap_or(false)
+}
+
+// membros manipulam a sessão do grupo; exige auth de quem chama
+fn require_member(env: &Env, group: &Symbol, member: &Address) {
+    group_owner(env, group);
+    if !is_group_member(env, group, member) {
+        panic_with_error!(env, Error::Unauthorized);
+    }
+    member.require_auth();
+}
+
+// gestão de membros: admin ou dono do grupo
+fn require_group_manager(env: &Env, group: &Symbol, caller: &Address) {
+    let owner = group_owner(env, group);
+    if *caller != owner && *caller != load_admin(env) {
+        panic_with_error!(env, Error::Unauthorized);
+    }
+    caller.require_auth();
+}
+
+// registra `name` na lista de slots do owner (uma vez)
+fn register_session_name(env: &Env, owner: &Address, name: &Symbol) {
+    let key = DataKeyExt::SessionNames(owner.clone());
+    let mut names: Vec<Symbol> = env
+        .storage()
+        .persistent()
+        .get(&key)
+        .unwrap_or(Vec::new(env));
+    if !names.contains(name) {
+        names.push_back(name.clone());
+        env.storage().persistent().set(&key, &names);
+    }
+}
+
+// marca a ordem como reembolsada e devolve ao owner o valor ainda retido
+// (`price_paid` menos a taxa já cobrada) a partir de onde o dinheiro está:
+// contrato (escrow) ou tesouraria (exige assinatura dela)
+fn refund_order(env: &Env, owner: &Address, order_id: u128, ord: &mut OrderRec) {
+    ord.refunded = true;
+    save_order(env, owner, order_id, ord);
+    bump_open_orders(env, owner, false, order_held(ord));
+
+    let token = TokenClient::new(env, &ord.token);
+    let from = if ord.escrowed {
+        env.current_contract_address()
+    } else {
+        let treasury = treasury(env);
+        treasury.require_auth();
+        treasury
+    };
+    let amount = order_held(ord);
+    token.transfer(&from, owner, &amount);
+    publish(
+        env,
+        (symbol_short!("refund"), owner.clone(), order_id),
+        amount,
+    );
+}
+
+// -------------------------------------------------------------
+// EVENTOS
+// -------------------------------------------------------------
+// fn emit_purchase_created(env: &Env, owner: &Address, package_id: u32, order_id: u128, price: i128) {
+//     env.events().publish(
+//         (symbol_short!("purchase"), symbol_short!("created")),
+//         (owner, package_id, order_id, price),
+//     );
+// }
+// próximo valor do EventSeq; renova o TTL da instância a cada evento
+fn next_event_seq(env: &Env) -> u64 {
+    let inst = env.storage().instance();
+    let seq: u64 = inst.get(&DataKey::EventSeq).unwrap_or(0) + 1;
+    inst.set(&DataKey::EventSeq, &seq);
+    inst.extend_ttl(INSTANCE_TTL_THRESHOLD, INSTANCE_TTL_EXTEND_TO);
+    seq
+}
+
+// trilha de auditoria da governança: todo setter de configuração do admin
+// publica ("cfg", chave) com (antigo, novo)
+fn publish_cfg<V: IntoVal<Env, Val>>(env: &Env, key: &str, old: V, new: V) {
+    let data: (Val, Val) = (old.into_val(env), new.into_val(env));
+    publish(env, (symbol_short!("cfg"), Symbol::new(env, key)), data);
+}
+
+// publica o evento com o EventSeq anexado como último tópico, para o indexador
+// conferir que recebeu uma sequência contígua
+fn publish<T, D>(env: &Env, topics: T, data: D)
+where
+    T: IntoVal<Env, Vec<Val>>,
+    D: IntoVal<Env, Val>,
+{
+    let mut topics: Vec<Val> = topics.into_val(env);
+    topics.push_back(next_event_seq(env).into_val(env));
+    env.events().publish(topics, data);
+}
+
+// checagens de assinatura do `grant_signed` (e do `require_grant_sig`): a
+// assinatura é do owner, então conta como self-grant; o nonce não é consumido
+fn require_grant_sig_of(env: &Env, owner: &Address, order_id: u128, sig: &BytesN<64>, nonce: u64) {
+    if !self_grant_allowed(env) {
+        panic_with_error!(env, Error::Unauthorized);
+    }
+    let key: BytesN<32> = env
+        .storage()
+        .persistent()
+        .get(&DataKey::SignerKey(owner.clone()))
+        .unwrap_or_else(|| panic_with_error!(env, Error::Unauthorized));
+    let next: u64 = env
+        .storage()
+        .persistent()
+        .get(&DataKey::GrantNonce(owner.clone()))
+        .unwrap_or(0);
+    if nonce < next {
+        panic_with_error!(env, Error::NonceReused);
+    }
+    let msg = grant_sig_payload(env, owner, order_id, nonce);
+    env.crypto().ed25519_verify(&key, &msg, sig);
+}
+
+// mensagem assinada pelo owner em `grant_signed`: XDR do endereço deste
+// contrato || XDR do owner || order_id (16 bytes BE) || nonce (8 bytes BE), para
+// a assinatura não valer em outra instância nem para outro owner
+fn grant_sig_payload(env: &Env, owner: &Address, order_id: u128, nonce: u64) -> Bytes {
+    let mut msg = env.current_contract_address().to_xdr(env);
+    msg.append(&owner.clone().to_xdr(env));
+    msg.extend_from_array(&order_id.to_be_bytes());
+    msg.extend_from_array(&nonce.to_be_bytes());
+    msg
+}
+
+fn emit_grant(env: &Env, owner: &Address, order_id: u128, new_remaining: u64) {
+    publish(
+        env,
+        (symbol_short!("grant"), owner, order_id),
+        new_remaining,
+    );
+}
+
+// -------------------------------------------------------------
+// CONTRATO
+// -------------------------------------------------------------
+#[contract]
+pub struct AccessTime;
+
+#[contractimpl]
+impl AccessTime {
+    // -------------------- init / set_package (inalterados) --------------------
+    /// Inicialização atômica no deploy: com `admin` e `token` informados o
+    /// contrato já nasce configurado, sem a janela entre deploy e `init`. Sem
+    /// argumentos (`None`, `None`) mantém o fluxo antigo de `init` separado.
+    pub fn __constructor(env: Env, admin: Option<Address>, token: Option<Address>) {
+        match (admin, token) {
+            (Some(admin), Some(token)) => Self::init(env, admin, token),
+            (None, None) => {}
+            _ => panic_with_error!(&env, Error::NotInitialized),
+        }
+    }
+
+    pub fn init(env: Env, admin: Address, xml_asset: Address) {
+        if env.storage().instance().has(&DataKey::Admin) {
+            panic_with_error!(&env, Error::AlreadyInitialized);
+        }
+        env.storage().instance().set(&DataKey::Admin, &admin);
+        env.storage().instance().set(&DataKey::Token, &xml_asset);
+        publish(&env, (symbol_short!("init"), admin.clone()), xml_asset);
+    }
+
+    /// `init` idempotente p/ ferramentas de deploy: inicializa se ainda não foi e
+    /// devolve o admin gravado. Já inicializado com o mesmo admin/token é no-op;
+    /// qualquer divergência reverte com `AlreadyInitialized`.
+    pub fn ensure_init(env: Env, admin: Address, token: Address) -> Address {
+        if !env.storage().instance().has(&DataKey::Admin) {
+            Self::init(env, admin.clone(), token);
+            return admin;
+        }
+        let stored = load_admin(&env);
+        if stored != admin || load_token(&env) != token {
+            panic_with_error!(&env, Error::AlreadyInitialized);
+        }
+        stored
+    }
+
+    /// Troca o token de pagamento das próximas compras (admin). Ordens já feitas
+    /// guardam o token em que foram pagas e são liberadas/reembolsadas nele.
+    /// Reverte com `TokenInUse` enquanto houver stake, crédito pré-pago, sessão
+    /// medida em aberto ou passe ainda não expirado, que o contrato pagaria no
+    /// token atual. A reserva fica registrada por token (`withdraw_reserve_in`).
+    pub fn set_token(env: Env, token: Address) {
+        require_admin(&env);
+        ensure_token_unused(&env);
+        let old = load_token(&env);
+        // a reserva do token que sai vai para ReserveOf e a do que volta (se já
+        // foi usado antes) passa a ser a do token atual
+        let kept = reserve_balance(&env);
+        let back = reserve_in(&env, &token);
+        env.storage().instance().set(&DataKey::Token, &token);
+        if old != token {
+            set_reserve_in(&env, &old, kept);
+            set_reserve_in(&env, &token, back);
+            env.storage()
+                .instance()
+                .remove(&DataKeyExt::ReserveOf(token.clone()));
+        }
+        publish_cfg(&env, "token", old, token);
+    }
+
+    /// `init` cobrando em XLM nativo: resolve o endereço do SAC nativo pelo `env`
+    /// e o grava como token. O restante do fluxo (buy_order etc.) não muda.
+    pub fn init_native(env: Env, admin: Address) {
+        let native = env
+            .deployer()
+            .with_stellar_asset(Bytes::from_array(&env, &NATIVE_ASSET_XDR))
+            .deployed_address();
+        Self::init(env, admin, native);
+    }
+
+    pub fn set_package(env: Env, id: u32, price: i128, duration_secs: u32) {
+        require_admin(&env);
+        ensure_catalog_mutable(&env);
+        validate_package(&env, duration_secs);
+        store_package(&env, id, price, duration_secs);
+    }
+
+    /// Congela o catálogo para sempre (admin, sem volta): dali em diante nenhum
+    /// pacote pode ser criado, editado, removido ou ter regras de venda/faixas
+    /// alteradas. Compras e grants seguem normais.
+    pub fn freeze_catalog(env: Env) {
+        require_admin(&env);
+        ensure_catalog_mutable(&env);
+        env.storage()
+            .instance()
+            .set(&DataKeyExt::CatalogFrozen, &true);
+        publish_cfg(&env, "catalog_frozen", false, true);
+    }
+
+    pub fn is_catalog_frozen(env: Env) -> bool {
+        catalog_frozen(&env)
+    }
+
+    /// Define o pacote usado por `buy_default` (admin); o pacote precisa existir.
+    pub fn set_default_package(env: Env, package_id: u32) {
+        require_admin(&env);
+        if !env.storage().instance().has(&DataKey::Package(package_id)) {
+            panic_with_error!(&env, Error::PackageNotFound);
+        }
+        let old: Option<u32> = env.storage().instance().get(&DataKey::DefaultPackage);
+        env.storage()
+            .instance()
+            .set(&DataKey::DefaultPackage, &package_id);
+        publish_cfg(&env, "default_package", old, Some(package_id));
+    }
+
+    /// Remove um pacote do catálogo (admin). Ordens pendentes que apontam para ele
+    /// deixam de ser creditáveis e o `grant` reverte com `PackageNotFound`.
+    pub fn delete_package(env: Env, id: u32) {
+        require_admin(&env);
+        ensure_catalog_mutable(&env);
+        if !env.storage().instance().has(&DataKey::Package(id)) {
+            panic_with_error!(&env, Error::PackageNotFound);
+        }
+        env.storage().instance().remove(&DataKey::Package(id));
+        env.storage().instance().remove(&DataKey::PackageSale(id));
+        env.storage()
+            .instance()
+            .remove(&DataKeyExt::PackageUpdatedAt(id));
+        let mut ids = package_ids(&env);
+        if let Some(i) = ids.first_index_of(id) {
+            ids.remove(i);
+            env.storage().instance().set(&DataKeyExt::PackageIds, &ids);
+        }
+        publish(&env, (symbol_short!("pkg_del"), id), ());
+    }
+
+    /// Cadastra vários pacotes numa única chamada (admin). Atômico: todas as
+    /// entradas são validadas antes de qualquer escrita.
+    pub fn set_packages(env: Env, packages: Vec<(u32, i128, u32)>) {
+        require_admin(&env);
+        ensure_catalog_mutable(&env);
+        for (_, _, duration_secs) in packages.iter() {
+            validate_package(&env, duration_secs);
+        }
+        for (id, price, duration_secs) in packages.iter() {
+            store_package(&env, id, price, duration_secs);
+        }
+    }
+
+    /// Ajusta o teto de `duration_secs` aceito por `set_package` (proteção contra
+    /// planos criados por engano com durações absurdas).
+    pub fn set_max_package_duration(env: Env, max_secs: u32) {
+        require_admin(&env);
+        let old = max_package_duration(&env);
+        env.storage()
+            .instance()
+            .set(&DataKey::MaxPackageDurationSecs, &max_secs);
+        publish_cfg(&env, "max_package_duration", old, max_secs);
+    }
+
+    pub fn get_max_package_duration(env: Env) -> u32 {
+        max_package_duration(&env)
+    }
+
+    /// Define as regras de venda do pacote (admin): habilitado, janela
+    /// `[starts_at, ends_at)` (0 = sem limite) e estoque (`None` = ilimitado).
+    pub fn set_package_sale(
+        env: Env,
+        id: u32,
+        enabled: bool,
+        starts_at: u64,
+        ends_at: u64,
+        stock: Option<u32>,
+    ) {
+        require_admin(&env);
+        ensure_catalog_mutable(&env);
+        if !env.storage().instance().has(&DataKey::Package(id)) {
+            panic_with_error!(&env, Error::PackageNotFound);
+        }
+        if ends_at != 0 && ends_at <= starts_at {
+            panic_with_error!(&env, Error::InvalidPackage);
+        }
+        let rules = SaleRules {
+            enabled,
+            starts_at,
+            ends_at,
+            stock,
+        };
+        let old = sale_rules(&env, id);
+        env.storage()
+            .instance()
+            .set(&DataKey::PackageSale(id), &rules);
+        publish_cfg(&env, "package_sale", (id, old), (id, Some(rules)));
+    }
+
+    /// Indica se o pacote pode ser comprado agora: existe, está habilitado, dentro
+    /// da janela de venda, com estoque e o contrato não está pausado. Nunca entra
+    /// em pânico.
+    pub fn is_package_available(env: Env, package_id: u32) -> bool {
+        !is_paused(&env)
+            && env.storage().instance().has(&DataKey::Package(package_id))
+            && check_on_sale(&env, package_id).is_ok()
+    }
+
+    pub fn get_package(env: Env, package_id: u32) -> Package {
+        env.storage()
+            .instance()
+            .get(&DataKey::Package(package_id))
+            .unwrap_or_else(|| panic_with_error!(&env, Error::PackageNotFound))
+    }
+
+    /// Só o preço do pacote (retorno menor que `get_package`).
+    pub fn package_price(env: Env, package_id: u32) -> i128 {
+        Self::get_package(env, package_id).price
+    }
+
+    /// Só a duração do pacote, em segundos.
+    pub fn package_duration(env: Env, package_id: u32) -> u32 {
+        Self::get_package(env, package_id).duration_secs
+    }
+
+    /// Teto de segundos consumidos por owner em cada dia UTC (admin); 0 desliga.
+    /// O `start` recusa com `DailyCapReached` o teto já atingido e deixa a
+    /// execução rodar só o que resta dele no dia: o excedente do saldo fica
+    /// retido e volta à sessão quando ela para. A consolidação também para a
+    /// execução que atingir o teto.
+    pub fn set_daily_cap_secs(env: Env, secs: u64) {
+        require_admin(&env);
+        let old = daily_cap_secs(&env);
+        env.storage()
+            .instance()
+            .set(&DataKeyExt::DailyCapSecs, &secs);
+        publish_cfg(&env, "daily_cap_secs", old, secs);
+    }
+
+    /// Segundos já consumidos hoje (dia UTC) pelo owner, contados para o teto
+    /// diário. A execução em aberto não entra.
+    pub fn daily_usage(env: Env, owner: Address) -> u64 {
+        daily_usage_of(&env, &owner, env.ledger().timestamp())
+    }
+
+    /// Arredonda para cima o consumo de cada execução ao múltiplo de `secs`, ex.:
+    /// 60 cobra por minuto iniciado (admin). O arredondamento sai uma vez, quando
+    /// a execução termina, sobre o tempo exato desde o start; `settle` e créditos
+    /// no meio debitam só o tempo exato. 0 ou 1 desliga.
+    pub fn set_consumption_round_secs(env: Env, secs: u64) {
+        require_admin(&env);
+        let old: u64 = env
+            .storage()
+            .instance()
+            .get(&DataKeyExt::ConsumptionRoundSecs)
+            .unwrap_or(0);
+        env.storage()
+            .instance()
+            .set(&DataKeyExt::ConsumptionRoundSecs, &secs);
+        publish_cfg(&env, "consumption_round_secs", old, secs);
+    }
+
+    /// Espera mínima entre a compra e o grant de uma ordem (admin), para dar
+    /// tempo às checagens antifraude. 0 desliga.
+    pub fn set_grant_delay_secs(env: Env, secs: u64) {
+        require_admin(&env);
+        let old: u64 = env
+            .storage()
+            .instance()
+            .get(&DataKeyExt::GrantDelaySecs)
+            .unwrap_or(0);
+        env.storage()
+            .instance()
+            .set(&DataKeyExt::GrantDelaySecs, &secs);
+        publish_cfg(&env, "grant_delay_secs", old, secs);
+    }
+
+    /// Intervalo mínimo entre grants do mesmo pacote para um owner (admin);
+    /// 0 desliga.
+    pub fn set_package_cooldown(env: Env, id: u32, secs: u64) {
+        require_admin(&env);
+        let old: u64 = env
+            .storage()
+            .instance()
+            .get(&DataKeyExt::PackageCooldownSecs(id))
+            .unwrap_or(0);
+        env.storage()
+            .instance()
+            .set(&DataKeyExt::PackageCooldownSecs(id), &secs);
+        publish_cfg(&env, "package_cooldown", (id, old), (id, secs));
+    }
+
+    /// Faixas de preço por quantidade do pacote (admin): `(min_qty, discount_bps)`
+    /// com `min_qty` estritamente crescente. Vetor vazio remove as faixas.
+    pub fn set_package_tiers(env: Env, id: u32, tiers: Vec<(u32, u32)>) {
+        require_admin(&env);
+        ensure_catalog_mutable(&env);
+        let mut prev: Option<u32> = None;
+        for (min_qty, bps) in tiers.iter() {
+            if bps as i128 > BPS_DENOMINATOR || prev.is_some_and(|p| min_qty <= p) {
+                panic_with_error!(&env, Error::InvalidFee);
+            }
+            prev = Some(min_qty);
+        }
+        let old: Vec<(u32, u32)> = env
+            .storage()
+            .instance()
+            .get(&DataKeyExt::PriceTiers(id))
+            .unwrap_or(Vec::new(&env));
+        publish_cfg(&env, "package_tiers", (id, old), (id, tiers.clone()));
+        if tiers.is_empty() {
+            env.storage().instance().remove(&DataKeyExt::PriceTiers(id));
+        } else {
+            env.storage()
+                .instance()
+                .set(&DataKeyExt::PriceTiers(id), &tiers);
+        }
+    }
+
+    /// Estatísticas de consumo por plano: `(vezes_creditado, total_segundos_creditados)`.
+    pub fn package_stats(env: Env, id: u32) -> (u32, u64) {
+        package_stats_of(&env, id)
+    }
+
+    /// Define quem recebe os pagamentos (admin).
+    pub fn set_treasury(env: Env, treasury: Address) {
+        require_admin(&env);
+        let old = self::treasury(&env);
+        env.storage().instance().set(&DataKey::Treasury, &treasury);
+        publish_cfg(&env, "treasury", old, treasury);
+    }
+
+    pub fn get_treasury(env: Env) -> Address {
+        treasury(&env)
+    }
+
+    /// Liga/desliga o modo escrow: com ele ligado, `buy_order` retém o pagamento
+    /// no contrato, `grant` libera para a tesouraria e `cancel_order` reembolsa.
+    pub fn set_escrow_mode(env: Env, enabled: bool) {
+        require_admin(&env);
+        let old = escrow_mode(&env);
+        env.storage().instance().set(&DataKey::EscrowMode, &enabled);
+        publish_cfg(&env, "escrow_mode", old, enabled);
+    }
+
+    /// Configura a divisão de cada pagamento entre parceiros por peso em bps (admin).
+    /// Os pesos devem somar 10000; um vetor vazio volta a pagar tudo à tesouraria.
+    pub fn set_splits(env: Env, splits: Vec<(Address, u32)>) {
+        require_admin(&env);
+        let old = self::splits(&env);
+        if splits.is_empty() {
+            env.storage().instance().remove(&DataKey::Splits);
+        } else {
+            let total: u64 = splits.iter().map(|(_, bps)| bps as u64).sum();
+            if total != BPS_DENOMINATOR as u64 {
+                panic_with_error!(&env, Error::InvalidFee);
+            }
+            env.storage().instance().set(&DataKey::Splits, &splits);
+        }
+        publish_cfg(&env, "splits", old, splits);
+    }
+
+    pub fn get_splits(env: Env) -> Vec<(Address, u32)> {
+        splits(&env)
+    }
+
+    /// Configuração de cobrança vigente numa leitura só:
+    /// `(fee_bps, fee_collector, splits)`, com os padrões quando não definidos.
+    pub fn fee_config(env: Env) -> (u32, Address, Vec<(Address, u32)>) {
+        (fee_bps(&env), fee_collector(&env), splits(&env))
+    }
+
+    /// Versão do layout dos eventos publicados (ver `EVENT_SCHEMA_VERSION`).
+    pub fn event_schema_version() -> u32 {
+        EVENT_SCHEMA_VERSION
+    }
+
+    /// Número de códigos de erro do contrato (1..=N), para integradores validarem
+    /// o mapeamento que usam.
+    pub fn error_code_count() -> u32 {
+        ERROR_CODE_COUNT
+    }
+
+    /// Tempo mínimo (segundos) que a sessão, pré-paga ou medida, precisa rodar
+    /// desde o `start` para aceitar `pause` (admin). Zero desliga a checagem.
+    pub fn set_min_active_secs(env: Env, secs: u64) {
+        require_admin(&env);
+        let old: u64 = env
+            .storage()
+            .instance()
+            .get(&DataKeyExt::MinActiveSecs)
+            .unwrap_or(0);
+        env.storage()
+            .instance()
+            .set(&DataKeyExt::MinActiveSecs, &secs);
+        publish_cfg(&env, "min_active_secs", old, secs);
+    }
+
+    /// Saldo mínimo (segundos) para `start` aceitar iniciar a sessão (admin).
+    /// Zero desliga a checagem.
+    pub fn set_min_start_secs(env: Env, secs: u64) {
+        require_admin(&env);
+        let old: u64 = env
+            .storage()
+            .instance()
+            .get(&DataKey::MinStartSecs)
+            .unwrap_or(0);
+        env.storage().instance().set(&DataKey::MinStartSecs, &secs);
+        publish_cfg(&env, "min_start_secs", old, secs);
+    }
+
+    /// Teto do saldo de uma sessão (admin). Com `clamp`, créditos que passariam do
+    /// teto são truncados; sem ele, revertem com `SessionCapExceeded`. 0 desliga.
+    pub fn set_max_session_secs(env: Env, max_secs: u64, clamp: bool) {
+        require_admin(&env);
+        let inst = env.storage().instance();
+        let old: (u64, bool) = (
+            inst.get(&DataKey::MaxSessionSecs).unwrap_or(0),
+            inst.get(&DataKey::CapClamp).unwrap_or(false),
+        );
+        inst.set(&DataKey::MaxSessionSecs, &max_secs);
+        inst.set(&DataKey::CapClamp, &clamp);
+        publish_cfg(&env, "max_session_secs", old, (max_secs, clamp));
+    }
+
+    /// Fração (bps) de cada pagamento retida no contrato como reserva para
+    /// reembolsos (admin). Precisa ficar em 0..=10000.
+    pub fn set_reserve_bps(env: Env, bps: u32) {
+        require_admin(&env);
+        if bps as i128 > BPS_DENOMINATOR {
+            panic_with_error!(&env, Error::InvalidFee);
+        }
+        let old = reserve_bps(&env);
+        env.storage().instance().set(&DataKey::ReserveBps, &bps);
+        publish_cfg(&env, "reserve_bps", old, bps);
+    }
+
+    pub fn get_reserve_balance(env: Env) -> i128 {
+        reserve_balance(&env)
+    }
+
+    /// Reserva retida em `token`, que pode ser um token anterior ao atual: ordens
+    /// em escrow liberadas depois do `set_token` retêm no token em que foram pagas.
+    pub fn get_reserve_balance_in(env: Env, token: Address) -> i128 {
+        reserve_in(&env, &token)
+    }
+
+    /// Libera `amount` da reserva retida no token atual para a tesouraria (admin).
+    pub fn withdraw_reserve(env: Env, amount: i128) {
+        Self::withdraw_reserve_in(env.clone(), load_token(&env), amount);
+    }
+
+    /// `withdraw_reserve` da reserva retida em `token` (admin), paga nesse token.
+    pub fn withdraw_reserve_in(env: Env, token: Address, amount: i128) {
+        require_admin(&env);
+        if amount <= 0 {
+            panic_with_error!(&env, Error::InvalidAmount);
+        }
+        let reserve = reserve_in(&env, &token);
+        if amount > reserve {
+            panic_with_error!(&env, Error::InsufficientBalance);
+        }
+        set_reserve_in(&env, &token, reserve - amount);
+        let to = treasury(&env);
+        TokenClient::new(&env, &token).transfer(&env.current_contract_address(), &to, &amount);
+        publish(&env, (symbol_short!("rsv_out"), to), amount);
+    }
+
+    /// Permite (padrão) ou proíbe o admin de creditar ordens de outros owners
+    /// (admin). Desligado, só o próprio owner chama a família `grant`.
+    pub fn set_admin_grant_allowed(env: Env, allowed: bool) {
+        require_admin(&env);
+        let old = admin_grant_allowed(&env);
+        env.storage()
+            .instance()
+            .set(&DataKey::AdminGrantAllowed, &allowed);
+        publish_cfg(&env, "admin_grant_allowed", old, allowed);
+    }
+
+    /// Permite (padrão) ou proíbe o owner de creditar as próprias ordens
+    /// (admin). Desligado, só o admin chama a família `grant` (ex.: KYC off-chain).
+    pub fn set_self_grant_allowed(env: Env, allowed: bool) {
+        require_admin(&env);
+        let old = self_grant_allowed(&env);
+        env.storage()
+            .instance()
+            .set(&DataKeyExt::SelfGrantAllowed, &allowed);
+        publish_cfg(&env, "self_grant_allowed", old, allowed);
+    }
+
+    /// Modo estrito (admin): o grant reverte com `PackageChanged` se o pacote
+    /// da ordem foi editado (`set_package`) depois da compra.
+    pub fn set_strict_grant(env: Env, strict: bool) {
+        require_admin(&env);
+        let old: bool = env
+            .storage()
+            .instance()
+            .get(&DataKeyExt::StrictGrant)
+            .unwrap_or(false);
+        env.storage()
+            .instance()
+            .set(&DataKeyExt::StrictGrant, &strict);
+        publish_cfg(&env, "strict_grant", old, strict);
+    }
+
+    /// Teto de ordens pendentes por owner (admin); 0 desliga. Um grant completo
+    /// ou um reembolso libera a vaga.
+    pub fn set_max_open_orders(env: Env, max: u32) {
+        require_admin(&env);
+        let old = max_open_orders(&env);
+        env.storage().instance().set(&DataKey::MaxOpenOrders, &max);
+        publish_cfg(&env, "max_open_orders", old, max);
+    }
+
+    pub fn open_orders(env: Env, owner: Address) -> u32 {
+        open_orders_of(&env, &owner)
+    }
+
+    /// Soma do valor ainda reembolsável (`price_paid` menos a taxa já cobrada)
+    /// das ordens pendentes do owner com id em `[start, start + limit)`.
+    pub fn refund_liability(env: Env, owner: Address, start: u128, limit: u32) -> i128 {
+        let last = last_order_id(&env, &owner);
+        let end = start.saturating_add(limit as u128);
+        let mut total: i128 = 0;
+        let mut id = start.max(1);
+        while id <= last && id < end {
+            if let Some(ord) = load_order(&env, &owner, id) {
+                if order_status(&ord) == OrderStatus::Pending {
+                    total += order_held(&ord);
+                }
+            }
+            id += 1;
+        }
+        total
+    }
+
+    /// Total global reembolsável das ordens pendentes, mantido a cada compra,
+    /// crédito e reembolso (para checar solvência sem varrer ordens).
+    pub fn open_order_value(env: Env) -> i128 {
+        open_order_value_of(&env)
+    }
+
+    /// Aponta uma ordem ainda não creditada para outro pacote (admin), para
+    /// corrigir compras de pacote errado. O valor pago não muda.
+    pub fn admin_repoint_order(env: Env, owner: Address, order_id: u128, new_package_id: u32) {
+        require_admin(&env);
+        let mut ord = load_order(&env, &owner, order_id)
+            .unwrap_or_else(|| panic_with_error!(&env, Error::OrderNotFound));
+        if ord.credited {
+            panic_with_error!(&env, Error::AlreadyGranted);
+        }
+        if ord.refunded {
+            panic_with_error!(&env, Error::InvalidOrderState);
+        }
+        if !env
+            .storage()
+            .instance()
+            .has(&DataKey::Package(new_package_id))
+        {
+            panic_with_error!(&env, Error::PackageNotFound);
+        }
+        let old_package_id = ord.package_id;
+        ord.package_id = new_package_id;
+        save_order(&env, &owner, order_id, &ord);
+        publish(
+            &env,
+            (symbol_short!("repoint"), owner, order_id),
+            (old_package_id, new_package_id),
+        );
+    }
+
+    /// Numeração global de ordens (admin): ligada, os ids vêm de um contador
+    /// único para todos os owners e a ordem é gravada com o owner dentro.
+    /// Configure antes da primeira venda: ordens do outro modo ficam invisíveis.
+    pub fn set_global_order_mode(env: Env, enabled: bool) {
+        require_admin(&env);
+        let old = global_order_mode(&env);
+        env.storage()
+            .instance()
+            .set(&DataKeyExt::GlobalOrderMode, &enabled);
+        publish_cfg(&env, "global_order_mode", old, enabled);
+    }
+
+    /// Ordem pelo id global, com o owner (só no modo global).
+    pub fn global_order(env: Env, order_id: u128) -> Option<(Address, OrderRec)> {
+        env.storage()
+            .persistent()
+            .get(&DataKeyExt::GlobalOrder(order_id))
+    }
+
+    /// Reposiciona o contador de ordens do owner (admin) logo acima da maior ordem
+    /// ainda gravada (varrendo de cima para baixo), nunca em 0: o próximo
+    /// `order_id` não colide com nenhuma ordem existente. Devolve esse próximo id.
+    pub fn reset_order_counter(env: Env, owner: Address) -> u128 {
+        require_admin(&env);
+        // contador global é compartilhado: não se reposiciona por owner
+        if global_order_mode(&env) {
+            return last_order_id(&env, &owner) + 1;
+        }
+        let mut id = last_order_id(&env, &owner);
+        while id > 0 && load_order(&env, &owner, id).is_none() {
+            id -= 1;
+        }
+        env.storage()
+            .instance()
+            .set(&DataKey::NextOrder(owner), &id);
+        id + 1
+    }
+
+    /// Resumo para recibos: (ordens criadas, ordens totalmente creditadas).
+    pub fn order_summary(env: Env, owner: Address) -> (u128, u32) {
+        let credited: u32 = env
+            .storage()
+            .persistent()
+            .get(&DataKey::CreditedCount(owner.clone()))
+            .unwrap_or(0);
+        let created: u128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::NextOrder(owner))
+            .unwrap_or(0);
+        (created, credited)
+    }
+
+    /// Taxa da plataforma sobre cada ordem (admin): `bps` do preço vão para
+    /// `collector`, cobrados na compra ou no grant conforme `timing`.
+    pub fn set_fee(env: Env, collector: Address, bps: u32, timing: FeeTiming) {
+        require_admin(&env);
+        if bps as i128 > BPS_DENOMINATOR {
+            panic_with_error!(&env, Error::InvalidFee);
+        }
+        let old = (fee_collector(&env), fee_bps(&env), fee_timing(&env));
+        let inst = env.storage().instance();
+        inst.set(&DataKey::FeeCollector, &collector);
+        inst.set(&DataKey::FeeBps, &bps);
+        inst.set(&DataKey::FeeTiming, &timing);
+        publish_cfg(&env, "fee", old, (collector, bps, timing));
+    }
+
+    /// Para onde vão as sobras da divisão inteira em taxa e splits (admin):
+    /// `true` (padrão) deixa do lado da tesouraria — taxa arredondada para baixo
+    /// e sobra dos splits para o primeiro recebedor; `false` manda ao coletor da
+    /// taxa. Em qualquer caso as partes somam exatamente o preço.
+    pub fn set_rounding_to_treasury(env: Env, to_treasury: bool) {
+        require_admin(&env);
+        let old = rounding_to_treasury(&env);
+        env.storage()
+            .instance()
+            .set(&DataKeyExt::RoundingToTreasury, &to_treasury);
+        publish_cfg(&env, "rounding_to_treasury", old, to_treasury);
+    }
+
+    /// Bloqueia/desbloqueia um owner (admin): bloqueado, não compra, não recebe
+    /// grants e não inicia sessões; `pause` continua liberado.
+    pub fn set_blocked(env: Env, owner: Address, blocked: bool) {
+        require_admin(&env);
+        env.storage()
+            .persistent()
+            .set(&DataKeyExt::Blocked(owner.clone()), &blocked);
+        publish(&env, (symbol_short!("blocked"), owner), blocked);
+    }
+
+    pub fn is_blocked(env: Env, owner: Address) -> bool {
+        is_blocked(&env, &owner)
+    }
+
+    /// Pausa/retoma o contrato (admin). Pausado, bloqueia compras, grants e
+    /// starts; `pause` de sessões continua liberado.
+    pub fn set_paused(env: Env, paused: bool) {
+        require_admin(&env);
+        let old = is_paused(&env);
+        env.storage().instance().set(&DataKey::Paused, &paused);
+        publish_cfg(&env, "paused", old, paused);
+    }
+
+    /// Toda a configuração de instância em uma chamada (verificação de deploy).
+    pub fn config(env: Env) -> ContractConfig {
+        let inst = env.storage().instance();
+        ContractConfig {
+            admin: load_admin(&env),
+            token: load_token(&env),
+            treasury: treasury(&env),
+            paused: is_paused(&env),
+            escrow_mode: escrow_mode(&env),
+            max_package_duration_secs: max_package_duration(&env),
+            max_session_secs: inst.get(&DataKey::MaxSessionSecs).unwrap_or(0),
+            cap_clamp: inst.get(&DataKey::CapClamp).unwrap_or(false),
+            min_start_secs: inst.get(&DataKey::MinStartSecs).unwrap_or(0),
+            refund_window_secs: inst.get(&DataKey::RefundWindowSecs).unwrap_or(0),
+            stake_threshold: inst.get(&DataKey::StakeThreshold).unwrap_or(0),
+            stake_discount_bps: inst.get(&DataKey::StakeDiscountBps).unwrap_or(0),
+            per_second_price: per_second_price(&env),
+            metered_min_secs: inst.get(&DataKey::MeteredMinSecs).unwrap_or(0),
+            default_package: inst.get(&DataKey::DefaultPackage),
+            splits: splits(&env),
+            reserve_bps: reserve_bps(&env),
+            admin_grant_allowed: admin_grant_allowed(&env),
+            max_open_orders: max_open_orders(&env),
+            fee_collector: fee_collector(&env),
+            fee_bps: fee_bps(&env),
+            fee_timing: fee_timing(&env),
+            rounding_to_treasury: rounding_to_treasury(&env),
+            self_grant_allowed: self_grant_allowed(&env),
+            credit_per_sec: inst.get(&DataKey::CreditPerSec).unwrap_or(0),
+            max_idle_secs: inst.get(&DataKeyExt::MaxIdleSecs).unwrap_or(0),
+            min_active_secs: inst.get(&DataKeyExt::MinActiveSecs).unwrap_or(0),
+            auto_settle: inst.get(&DataKeyExt::AutoSettle).unwrap_or(false),
+            catalog_frozen: catalog_frozen(&env),
+            global_order_mode: global_order_mode(&env),
+            grant_delay_secs: inst.get(&DataKeyExt::GrantDelaySecs).unwrap_or(0),
+            global_grace_secs: grace_config(&env).0,
+            daily_cap_secs: daily_cap_secs(&env),
+            consumption_round_secs: inst.get(&DataKeyExt::ConsumptionRoundSecs).unwrap_or(0),
+            strict_grant: inst.get(&DataKeyExt::StrictGrant).unwrap_or(false),
+        }
+    }
+
+    /// Exporta configuração e catálogo numa chamada só (admin; backup/auditoria):
+    /// `config()` mais todos os pacotes cadastrados, na ordem de cadastro.
+    pub fn export_catalog(env: Env) -> (ContractConfig, Vec<(u32, Package)>) {
+        require_admin(&env);
+        let mut packages = Vec::new(&env);
+        for id in package_ids(&env).iter() {
+            if let Some(pkg) = env
+                .storage()
+                .instance()
+                .get::<_, Package>(&DataKey::Package(id))
+            {
+                packages.push_back((id, pkg));
+            }
+        }
+        (Self::config(env), packages)
+    }
+
+    pub fn get_admin(env: Env) -> Address {
+        env.storage()
+            .instance()
+            .get(&DataKey::Admin)
+            .unwrap_or_else(|| panic_with_error!(&env, Error::NotInitialized))
+    }
+
+    pub fn get_token(env: Env) -> Address {
+        env.storage()
+            .instance()
+            .get(&DataKey::Token)
+            .unwrap_or_else(|| panic_with_error!(&env, Error::NotInitialized))
+    }
+
+    /// Lê ao vivo `decimals()` e `name()` do token (SAC) e devolve
+    /// `(fator_de_escala, decimals, name)`, ex.: XLM -> `(10_000_000, 7, "native")`.
+    /// Nada é cacheado aqui; decimals cujo fator não cabe em i128 revertem com
+    /// `InvalidTokenDecimals`.
+    pub fn token_metadata(env: Env) -> (i128, u32, String) {
+        let token = TokenClient::new(&env, &load_token(&env));
+        let decimals = token.decimals();
+        let scale = 10_i128
+            .checked_pow(decimals)
+            .unwrap_or_else(|| panic_with_error!(&env, Error::InvalidTokenDecimals));
+        (scale, decimals, token.name())
+    }
+
+    /// Último EventSeq publicado (0 = nenhum evento ainda).
+    pub fn event_seq(env: Env) -> u64 {
+        env.storage()
+            .instance()
+            .get(&DataKey::EventSeq)
+            .unwrap_or(0)
+    }
+
+    // -------------------- NOVO: buy_order (compra sem crédito) ----------------
+
+    fn dbg(env: &Env, step: &str) {
+        publish(env, (Symbol::new(env, "dbg"), Symbol::new(env, step)), ());
+    }
+
+    pub fn buy_order(env: Env, owner: Address, package_id: u32) -> u128 {
+        owner.require_auth();
+        Self::place_order(&env, &owner, package_id, Funding::Transfer, None, 0)
+    }
+
+    /// Compra o pacote padrão configurado pelo admin (quiosques de plano único).
+    pub fn buy_default(env: Env, owner: Address) -> u128 {
+        owner.require_auth();
+        let package_id: u32 = env
+            .storage()
+            .instance()
+            .get(&DataKey::DefaultPackage)
+            .unwrap_or_else(|| panic_with_error!(&env, Error::NoDefaultPackage));
+        Self::place_order(&env, &owner, package_id, Funding::Transfer, None, 0)
+    }
+
+    /// Compra puxando o pagamento de um allowance dado ao contrato (`transfer_from`
+    /// com o contrato como spender). O owner ainda autoriza a compra, mas a
+    /// entrada de auth assinada basta: um relayer pode submeter a transação.
+    pub fn buy_order_from_allowance(env: Env, owner: Address, package_id: u32) -> u128 {
+        owner.require_auth();
+        Self::place_order(&env, &owner, package_id, Funding::Allowance, None, 0)
+    }
+
+    /// `buy_order` com proteção de slippage: reverte com `PriceExceedsMax` se o
+    /// valor cobrado (já com descontos) passar de `max_price`.
+    pub fn buy_order_max(env: Env, owner: Address, package_id: u32, max_price: i128) -> u128 {
+        owner.require_auth();
+        Self::place_order(
+            &env,
+            &owner,
+            package_id,
+            Funding::Transfer,
+            Some(max_price),
+            0,
+        )
+    }
+
+    /// Compra `qty` unidades do pacote (uma ordem por unidade), com o desconto
+    /// da faixa de `set_package_tiers` que a quantidade atinge. Devolve os ids.
+    pub fn buy_order_bulk(env: Env, owner: Address, package_id: u32, qty: u32) -> Vec<u128> {
+        owner.require_auth();
+        if qty == 0 {
+            panic_with_error!(&env, Error::InvalidAmount);
+        }
+        if qty > MAX_BATCH {
+            panic_with_error!(&env, Error::BatchTooLarge);
+        }
+        let bps = tier_discount_bps(&env, package_id, qty);
+        let mut ids = Vec::new(&env);
+        for _ in 0..qty {
+            ids.push_back(Self::place_order(
+                &env,
+                &owner,
+                package_id,
+                Funding::Transfer,
+                None,
+                bps,
+            ));
+        }
+        ids
+    }
+
+    /// Pré-compra `count` ordens do pacote (máquinas de venda/IoT): cobra
+    /// `count * preço` numa transferência só e cria `count` ordens pendentes do
+    /// `payer`, para serem creditadas depois uma a uma. Devolve os ids.
+    pub fn prebuy_batch(env: Env, payer: Address, package_id: u32, count: u32) -> Vec<u128> {
+        payer.require_auth();
+        if count == 0 {
+            panic_with_error!(&env, Error::InvalidAmount);
+        }
+        if count > MAX_BATCH {
+            panic_with_error!(&env, Error::BatchTooLarge);
+        }
+        ensure_can_operate(&env, &payer);
+        let pkg = Self::get_package(env.clone(), package_id);
+        let total = quote_price(&env, &payer, pkg.price) * count as i128;
+        if total > 0 {
+            TokenClient::new(&env, &load_token(&env)).transfer(
+                &payer,
+                &env.current_contract_address(),
+                &total,
+            );
+        }
+        let mut ids = Vec::new(&env);
+        for _ in 0..count {
+            ids.push_back(Self::place_order(
+                &env,
+                &payer,
+                package_id,
+                Funding::Prepaid,
+                None,
+                0,
+            ));
+        }
+        ids
+    }
+
+    /// Quantas unidades do pacote o saldo de token do owner paga agora
+    /// (`saldo / preço`, com o desconto de stake da compra). Pacote gratuito
+    /// devolve 0; pacote inexistente reverte com `PackageNotFound`.
+    pub fn affordable_count(env: Env, owner: Address, package_id: u32) -> u32 {
+        let pkg = Self::get_package(env.clone(), package_id);
+        let price = quote_price(&env, &owner, pkg.price);
+        if price <= 0 {
+            return 0;
+        }
+        let balance = TokenClient::new(&env, &load_token(&env)).balance(&owner);
+        (balance.max(0) / price).min(u32::MAX as i128) as u32
+    }
+
+    /// Total que `buy_order_bulk` cobraria por `qty` unidades, com o desconto da
+    /// faixa de quantidade (sem o desconto de stake, que depende do owner).
+    pub fn bulk_quote(env: Env, package_id: u32, qty: u32) -> i128 {
+        if qty == 0 {
+            panic_with_error!(&env, Error::InvalidAmount);
+        }
+        if qty > MAX_BATCH {
+            panic_with_error!(&env, Error::BatchTooLarge);
+        }
+        let price = Self::get_package(env.clone(), package_id).price;
+        let bps = tier_discount_bps(&env, package_id, qty);
+        let unit = price - price * bps as i128 / BPS_DENOMINATOR;
+        unit * qty as i128
+    }
+
+    // cobra o pacote e registra a ordem pendente (auth do owner fica com o chamador);
+    // `funding`: de onde sai o pagamento (ver `Funding`);
+    // `max_price`: teto aceito pelo comprador; `tier_bps`: desconto por quantidade
+    fn place_order(
+        env: &Env,
+        owner: &Address,
+        package_id: u32,
+        funding: Funding,
+        max_price: Option<i128>,
+        tier_bps: u32,
+    ) -> u128 {
+        Self::dbg(env, "start");
+        ensure_can_operate(env, owner);
+
+        let token_id: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::Token)
+            .unwrap_or_else(|| {
+                Self::dbg(env, "err_no_token");
+                panic_with_error!(env, Error::NotInitialized)
+            });
+
+        let pkg: Package = env
+            .storage()
+            .instance()
+            .get(&DataKey::Package(package_id))
+            .unwrap_or_else(|| {
+                Self::dbg(env, "err_pkg_nf");
+                panic_with_error!(env, Error::PackageNotFound)
+            });
+        if let Err(e) = check_on_sale(env, package_id) {
+            panic_with_error!(env, e);
+        }
+        let max_open = max_open_orders(env);
+        if max_open > 0 && open_orders_of(env, owner) >= max_open {
+            panic_with_error!(env, Error::TooManyOpenOrders);
+        }
+
+        let mut price = quote_price(env, owner, pkg.price);
+        price -= price * tier_bps as i128 / BPS_DENOMINATOR;
+        if max_price.is_some_and(|max| price > max) {
+            panic_with_error!(env, Error::PriceExceedsMax);
+        }
+        let fee = fee_of(env, price);
+        let fee_at_grant = fee > 0 && fee_timing(env) == FeeTiming::OnGrant;
+        // modo escrow: o valor fica no contrato até o grant (ou volta no cancel_order);
+        // taxa no grant também exige reter o pagamento
+        let escrowed = escrow_mode(env) || fee_at_grant;
+
+        Self::dbg(env, "before_transfer");
+        let token = TokenClient::new(env, &token_id);
+        // se der erro do SAC, diagnostics mostram
+        let this = env.current_contract_address();
+        let from = match funding {
+            Funding::Transfer => owner.clone(),
+            Funding::Allowance => {
+                token.transfer_from(&this, owner, &this, &price);
+                this.clone()
+            }
+            Funding::Prepaid => this.clone(),
+        };
+        let fee_now = if fee_at_grant { 0 } else { fee };
+        charge(env, &token, &from, price, fee_now, escrowed);
+
+        Self::dbg(env, "after_transfer");
+
+        // >>>>> ALTERAÇÃO: gerar order_id determinístico pelo contador <<<<<
+        let order_id: u128 = next_order_id(env, owner);
+
+        let rec = OrderRec {
+            package_id,
+            credited: false,
+            price_paid: price,
+            escrowed,
+            refunded: false,
+            created_at: env.ledger().timestamp(),
+            credited_secs: 0,
+            fee,
+            fee_at_grant,
+            token: token_id,
+        };
+        save_order(env, owner, order_id, &rec);
+        let on_sale = in_sale_window(env, package_id);
+        consume_stock(env, package_id);
+        bump_open_orders(env, owner, true, order_held(&rec));
+
+        // corpo: (owner, package_id, order_id, price, on_sale)
+        publish(
+            env,
+            (Symbol::new(env, "purchase"), Symbol::new(env, "created")),
+            (owner.clone(), package_id, order_id, price, on_sale),
+        );
+        Self::dbg(env, "done");
+        order_id
+    }
+
+    /// Troca uma ordem pendente por um pacote maior numa só transação: a ordem
+    /// antiga é encerrada como reembolsada e o valor dela abate o preço do novo
+    /// pacote, cobrando só a diferença. A nova ordem herda o modo de pagamento
+    /// (escrow / momento da taxa) da antiga. Devolve o novo order_id.
+    pub fn upgrade_order(
+        env: Env,
+        owner: Address,
+        old_order_id: u128,
+        new_package_id: u32,
+    ) -> u128 {
+        owner.require_auth();
+        ensure_can_operate(&env, &owner);
+        let mut old = load_order(&env, &owner, old_order_id)
+            .unwrap_or_else(|| panic_with_error!(&env, Error::OrderNotFound));
+        if old.credited {
+            panic_with_error!(&env, Error::AlreadyGranted);
+        }
+        if old.refunded || old.credited_secs > 0 {
+            panic_with_error!(&env, Error::InvalidOrderState);
+        }
+        let pkg: Package = env
+            .storage()
+            .instance()
+            .get(&DataKey::Package(new_package_id))
+            .unwrap_or_else(|| panic_with_error!(&env, Error::PackageNotFound));
+        check_on_sale(&env, new_package_id).unwrap_or_else(|e| panic_with_error!(&env, e));
+
+        let price = quote_price(&env, &owner, pkg.price);
+        let diff = price - old.price_paid;
+        if diff < 0 {
+            panic_with_error!(&env, Error::InvalidAmount);
+        }
+        // taxa: no grant, recalculada sobre o novo preço; na compra, só a parte nova
+        let (fee, fee_now) = if old.fee_at_grant {
+            (fee_of(&env, price), 0)
+        } else {
+            let extra = (fee_of(&env, price) - old.fee).clamp(0, diff);
+            (old.fee + extra, extra)
+        };
+        // a diferença sai no mesmo token da ordem original
+        let token = TokenClient::new(&env, &old.token);
+        charge(&env, &token, &owner, diff, fee_now, old.escrowed);
+
+        old.refunded = true;
+        save_order(&env, &owner, old_order_id, &old);
+        bump_open_orders(&env, &owner, false, order_held(&old));
+
+        let order_id = next_order_id(&env, &owner);
+        let rec = OrderRec {
+            package_id: new_package_id,
+            credited: false,
+            price_paid: price,
+            escrowed: old.escrowed,
+            refunded: false,
+            created_at: env.ledger().timestamp(),
+            credited_secs: 0,
+            fee,
+            fee_at_grant: old.fee_at_grant,
+            token: old.token.clone(),
+        };
+        save_order(&env, &owner, order_id, &rec);
+        consume_stock(&env, new_package_id);
+        bump_open_orders(&env, &owner, true, order_held(&rec));
+        publish(
+            &env,
+            (symbol_short!("upgrade"), owner, old_order_id),
+            (order_id, diff),
+        );
+        order_id
+    }
+
+    // -------------------- NOVO: grant (owner OU admin) ------------------------
+    /// Credita os segundos do pacote na sessão do `owner` referentes à `order_id`.
+    /// Pode ser chamado pelo **owner** (self-serve) OU pelo **admin**.
+    /// - Idempotente: se já creditado, retorna erro `AlreadyGranted`.
+    pub fn grant(env: Env, caller: Address, owner: Address, order_id: u128) {
+        authorize_grant(&env, &caller, &owner);
+        apply_grant(&env, &owner, order_id);
+    }
+
+    /// `grant` em lote para várias ordens do owner, até `MAX_BATCH`. A
+    /// autorização do caller é exigida uma única vez e cobre o lote inteiro
+    /// (intencional); atômico. Devolve quantas ordens foram creditadas.
+    pub fn grant_all(env: Env, caller: Address, owner: Address, order_ids: Vec<u128>) -> u32 {
+        if order_ids.len() > MAX_BATCH {
+            panic_with_error!(&env, Error::BatchTooLarge);
+        }
+        authorize_grant(&env, &caller, &owner);
+        for order_id in order_ids.iter() {
+            apply_grant(&env, &owner, order_id);
+        }
+        order_ids.len()
+    }
+
+    /// Credita pares `(owner, order_id)` de vários owners numa chamada (admin),
+    /// até `MAX_BATCH`. Ordens já creditadas são puladas; qualquer outro erro
+    /// reverte o lote. Devolve quantas ordens foram creditadas.
+    pub fn admin_grant_batch(env: Env, grants: Vec<(Address, u128)>) -> u32 {
+        if grants.len() > MAX_BATCH {
+            panic_with_error!(&env, Error::BatchTooLarge);
+        }
+        let admin = load_admin(&env);
+        if !admin_grant_allowed(&env) {
+            panic_with_error!(&env, Error::Unauthorized);
+        }
+        admin.require_auth();
+        let mut credited = 0;
+        for (owner, order_id) in grants.iter() {
+            if load_order(&env, &owner, order_id).is_some_and(|o| o.credited) {
+                continue;
+            }
+            apply_grant(&env, &owner, order_id);
+            credited += 1;
+        }
+        credited
+    }
+
+    /// Variante do `grant` que, com `start_now`, já inicia a sessão após creditar.
+    pub fn grant_ex(env: Env, caller: Address, owner: Address, order_id: u128, start_now: bool) {
+        authorize_grant(&env, &caller, &owner);
+        apply_grant(&env, &owner, order_id);
+        if start_now {
+            start_session(&env, &owner);
+        }
+    }
+
+    /// Credita só `secs` da ordem (upgrades proporcionais). A ordem fica pendente
+    /// até o pacote inteiro ser creditado; passar do que falta reverte com
+    /// `InvalidOrderState`.
+    pub fn grant_partial(env: Env, caller: Address, owner: Address, order_id: u128, secs: u64) {
+        authorize_grant(&env, &caller, &owner);
+        credit_order(&env, &owner, order_id, Some(secs));
+    }
+
+    /// Variante do `grant` que agenda o saldo: `start` só é aceito a partir de
+    /// `not_before` (0 = imediato). Vale para todo o saldo da sessão.
+    pub fn grant_at(env: Env, caller: Address, owner: Address, order_id: u128, not_before: u64) {
+        authorize_grant(&env, &caller, &owner);
+        let mut s = apply_grant(&env, &owner, order_id);
+        s.not_before = not_before;
+        save_session(&env, &owner, &s);
+    }
+
+    /// Variante idempotente por referência externa: repetir a chamada com a mesma
+    /// `reference` para o mesmo owner é no-op e devolve o saldo do primeiro grant,
+    /// mesmo que o `order_id` seja outro (retries que regeneram ordens não creditam
+    /// duas vezes). Cada owner tem o próprio espaço de referências.
+    pub fn grant_with_ref(
+        env: Env,
+        caller: Address,
+        owner: Address,
+        order_id: u128,
+        reference: BytesN<32>,
+    ) -> u64 {
+        authorize_grant(&env, &caller, &owner);
+        let key = DataKey::GrantRef(owner.clone(), reference);
+        if let Some(prior) = env.storage().persistent().get::<_, u64>(&key) {
+            return prior;
+        }
+        let s = apply_grant(&env, &owner, order_id);
+        env.storage().persistent().set(&key, &s.remaining_secs);
+        s.remaining_secs
+    }
+
+    /// Registra a chave ed25519 com que o owner assina grants off-chain.
+    pub fn set_signer_key(env: Env, owner: Address, public_key: BytesN<32>) {
+        owner.require_auth();
+        env.storage()
+            .persistent()
+            .set(&DataKey::SignerKey(owner), &public_key);
+    }
+
+    /// Grant autorizado por assinatura (fluxo sem gás): qualquer relayer submete
+    /// `sig`, a assinatura ed25519 do owner sobre `XDR(contrato) || XDR(owner) ||
+    /// order_id (16 bytes BE) || nonce (8 bytes BE)`. O nonce precisa ser >= ao
+    /// próximo esperado e é consumido, o que impede replay.
+    pub fn grant_signed(env: Env, owner: Address, order_id: u128, sig: BytesN<64>, nonce: u64) {
+        require_grant_sig_of(&env, &owner, order_id, &sig, nonce);
+        env.storage()
+            .persistent()
+            .set(&DataKey::GrantNonce(owner.clone()), &(nonce + 1));
+        apply_grant(&env, &owner, order_id);
+    }
+
+    /// Pré-validação do relayer: reverte exatamente como `grant_signed`
+    /// reverteria para `sig` e `nonce`, sem consumir o nonce nem creditar nada.
+    /// `Unauthorized` sem chave registrada ou com o self-grant desligado,
+    /// `NonceReused` com nonce velho; assinatura inválida aborta no
+    /// `ed25519_verify` do host, que não tem variante sem trap. Simular a chamada
+    /// basta: sucesso quer dizer que o `grant_signed` passaria na assinatura.
+    pub fn require_grant_sig(
+        env: Env,
+        owner: Address,
+        order_id: u128,
+        sig: BytesN<64>,
+        nonce: u64,
+    ) {
+        require_grant_sig_of(&env, &owner, order_id, &sig, nonce);
+    }
+
+    /// Reemite o evento `grant` de uma ordem já creditada com o saldo atual (admin).
+    /// Não altera estado; serve só para reconciliação de indexadores.
+    pub fn reemit_grant(env: Env, owner: Address, order_id: u128) {
+        require_admin(&env);
+        let ord = load_order(&env, &owner, order_id)
+            .unwrap_or_else(|| panic_with_error!(&env, Error::OrderNotFound));
+        if !ord.credited {
+            panic_with_error!(&env, Error::InvalidOrderState);
+        }
+        let s = load_session(&env, &owner);
+        emit_grant(&env, &owner, order_id, s.remaining_secs);
+    }
+
+    // -------------------- stake (desconto) ------------------------------------
+    /// Configura o desconto para stakers (admin): quem tiver stake acima de
+    /// `threshold` paga `price - price * discount_bps / 10000`.
+    pub fn set_stake_discount(env: Env, threshold: i128, discount_bps: u32) {
+        require_admin(&env);
+        if discount_bps as i128 > BPS_DENOMINATOR {
+            panic_with_error!(&env, Error::InvalidFee);
+        }
+        let inst = env.storage().instance();
+        let old: (i128, u32) = (
+            inst.get(&DataKey::StakeThreshold).unwrap_or(0),
+            inst.get(&DataKey::StakeDiscountBps).unwrap_or(0),
+        );
+        inst.set(&DataKey::StakeThreshold, &threshold);
+        inst.set(&DataKey::StakeDiscountBps, &discount_bps);
+        publish_cfg(&env, "stake_discount", old, (threshold, discount_bps));
+    }
+
+    /// Deposita tokens em stake no contrato.
+    pub fn stake(env: Env, owner: Address, amount: i128) {
+        owner.require_auth();
+        if amount <= 0 {
+            panic_with_error!(&env, Error::InvalidAmount);
+        }
+        TokenClient::new(&env, &load_token(&env)).transfer(
+            &owner,
+            &env.current_contract_address(),
+            &amount,
+        );
+        let total = staked_of(&env, &owner) + amount;
+        env.storage()
+            .persistent()
+            .set(&DataKey::Staked(owner.clone()), &total);
+        bump_custody(&env, DataKeyExt::TotalStaked, amount);
+        publish(&env, (symbol_short!("stake"), owner), total);
+    }
+
+    /// Retira tokens do stake de volta para o owner.
+    pub fn unstake(env: Env, owner: Address, amount: i128) {
+        owner.require_auth();
+        if amount <= 0 {
+            panic_with_error!(&env, Error::InvalidAmount);
+        }
+        let staked = staked_of(&env, &owner);
+        if amount > staked {
+            panic_with_error!(&env, Error::InsufficientBalance);
+        }
+        env.storage()
+            .persistent()
+            .set(&DataKey::Staked(owner.clone()), &(staked - amount));
+        bump_custody(&env, DataKeyExt::TotalStaked, -amount);
+        TokenClient::new(&env, &load_token(&env)).transfer(
+            &env.current_contract_address(),
+            &owner,
+            &amount,
+        );
+        publish(&env, (symbol_short!("unstake"), owner), staked - amount);
+    }
+
+    pub fn get_staked(env: Env, owner: Address) -> i128 {
+        staked_of(&env, &owner)
+    }
+
+    // -------------------- crédito pré-pago ------------------------------------
+    /// Taxa de conversão crédito -> segundos usada por `buy_time_from_credit` (admin).
+    pub fn set_credit_rate(env: Env, credit_per_sec: i128) {
+        require_admin(&env);
+        if credit_per_sec <= 0 {
+            panic_with_error!(&env, Error::InvalidAmount);
+        }
+        let old: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::CreditPerSec)
+            .unwrap_or(0);
+        env.storage()
+            .instance()
+            .set(&DataKey::CreditPerSec, &credit_per_sec);
+        publish_cfg(&env, "credit_rate", old, credit_per_sec);
+    }
+
+    /// Deposita tokens como crédito pré-pago (custodiado no contrato).
+    pub fn deposit_credit(env: Env, owner: Address, amount: i128) {
+        owner.require_auth();
+        if amount <= 0 {
+            panic_with_error!(&env, Error::InvalidAmount);
+        }
+        TokenClient::new(&env, &load_token(&env)).transfer(
+            &owner,
+            &env.current_contract_address(),
+            &amount,
+        );
+        let total = credit_of(&env, &owner) + amount;
+        env.storage()
+            .persistent()
+            .set(&DataKey::Credit(owner.clone()), &total);
+        bump_custody(&env, DataKeyExt::TotalCredit, amount);
+        publish(&env, (symbol_short!("credit"), owner), total);
+    }
+
+    /// Saca crédito pré-pago de volta para o owner. O saldo é debitado antes da
+    /// transferência; acima do saldo, `InsufficientBalance`.
+    pub fn withdraw_credit(env: Env, owner: Address, amount: i128) {
+        owner.require_auth();
+        if amount <= 0 {
+            panic_with_error!(&env, Error::InvalidAmount);
+        }
+        let credit = credit_of(&env, &owner);
+        if amount > credit {
+            panic_with_error!(&env, Error::InsufficientBalance);
+        }
+        env.storage()
+            .persistent()
+            .set(&DataKey::Credit(owner.clone()), &(credit - amount));
+        bump_custody(&env, DataKeyExt::TotalCredit, -amount);
+        TokenClient::new(&env, &load_token(&env)).transfer(
+            &env.current_contract_address(),
+            &owner,
+            &amount,
+        );
+        publish(&env, (symbol_short!("credit"), owner), credit - amount);
+    }
+
+    pub fn get_credit(env: Env, owner: Address) -> i128 {
+        credit_of(&env, &owner)
+    }
+
+    /// Troca crédito por `secs` de sessão à taxa `CreditPerSec`, sem pacote. Com o
+    /// teto truncando, só os segundos de fato creditados são cobrados; o valor
+    /// debitado vai à tesouraria. Devolve o novo saldo da sessão.
+    pub fn buy_time_from_credit(env: Env, owner: Address, secs: u64) -> u64 {
+        owner.require_auth();
+        ensure_can_operate(&env, &owner);
+        let rate: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::CreditPerSec)
+            .unwrap_or(0);
+        if secs == 0 || rate <= 0 {
+            panic_with_error!(&env, Error::InvalidAmount);
+        }
+        let credit = credit_of(&env, &owner);
+        if (secs as i128) * rate > credit {
+            panic_with_error!(&env, Error::InsufficientBalance);
+        }
+
+        let (s, credited) = credit_session(&env, &owner, secs);
+        let cost = credited as i128 * rate;
+        env.storage()
+            .persistent()
+            .set(&DataKey::Credit(owner.clone()), &(credit - cost));
+        bump_custody(&env, DataKeyExt::TotalCredit, -cost);
+        if cost > 0 {
+            let token = TokenClient::new(&env, &load_token(&env));
+            pay_out(&env, &token, &env.current_contract_address(), cost);
+        }
+        publish(&env, (symbol_short!("cr_time"), owner), (credited, cost));
+        s.remaining_secs
+    }
+
+    /// Encerra o acesso do owner imediatamente (admin): zera saldo e início,
+    /// esteja a sessão rodando ou pausada. O saldo não é guardado.
+    pub fn admin_expire(env: Env, owner: Address) {
+        require_admin(&env);
+        let mut s = load_session(&env, &owner);
+        take_cap_hold(&env, &DataKey::Session(owner.clone()));
+        s.remaining_secs = 0;
+        s.started_at = 0;
+        save_session(&env, &owner, &s);
+        publish(&env, (symbol_short!("expired"), owner), ());
+    }
+
+    /// Credita `secs` de cortesia na sessão do owner (admin), sem ordem nem pagamento.
+    /// Emite `("comp", owner)` em vez de `grant` para as métricas separarem
+    /// tempo pago de tempo gratuito. Devolve o novo saldo.
+    pub fn admin_credit(env: Env, owner: Address, secs: u64) -> u64 {
+        require_admin(&env);
+        if secs == 0 {
+            panic_with_error!(&env, Error::InvalidAmount);
+        }
+        comp_credit(&env, &owner, secs).1.remaining_secs
+    }
+
+    /// `admin_credit` para vários owners numa chamada (admin), até `MAX_BATCH`,
+    /// com uma única autorização do admin para o lote. Atômico: se um crédito for rejeitado pelo teto, nenhum é aplicado.
+    /// Devolve quantos owners receberam segundos (com o teto truncando, pode
+    /// ser menos que o tamanho do lote).
+    pub fn admin_credit_many(env: Env, owners: Vec<Address>, secs: u64) -> u32 {
+        require_admin(&env);
+        if owners.len() > MAX_BATCH {
+            panic_with_error!(&env, Error::BatchTooLarge);
+        }
+        if secs == 0 {
+            panic_with_error!(&env, Error::InvalidAmount);
+        }
+        let mut count = 0u32;
+        for owner in owners.iter() {
+            if comp_credit(&env, &owner, secs).0 > 0 {
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Total de segundos já creditados pela origem informada (pago ou cortesia).
+    pub fn granted_secs(env: Env, source: GrantSource) -> u64 {
+        granted_secs_of(&env, source)
+    }
+
+    // -------------------- códigos promocionais --------------------------------
+    /// Cadastra (ou sobrescreve) um código promocional pelo seu sha256 (admin).
+    pub fn create_code(env: Env, code_hash: BytesN<32>, secs: u64, max_uses: u32) {
+        require_admin(&env);
+        let code = PromoCode {
+            secs,
+            uses_left: max_uses,
+        };
+        env.storage()
+            .persistent()
+            .set(&DataKey::PromoCode(code_hash.clone()), &code);
+        publish(
+            &env,
+            (symbol_short!("code_set"), code_hash),
+            (secs, max_uses),
+        );
+    }
+
+    /// Resgata um código em claro: credita `secs` na sessão do owner e consome um uso.
+    pub fn redeem_code(env: Env, owner: Address, code: Bytes) {
+        owner.require_auth();
+        ensure_can_operate(&env, &owner);
+        let key = DataKey::PromoCode(env.crypto().sha256(&code).to_bytes());
+        let mut promo: PromoCode = env
+            .storage()
+            .persistent()
+            .get(&key)
+            .unwrap_or_else(|| panic_with_error!(&env, Error::CodeNotFound));
+        if promo.uses_left == 0 {
+            panic_with_error!(&env, Error::CodeExhausted);
+        }
+        promo.uses_left -= 1;
+        env.storage().persistent().set(&key, &promo);
+
+        let (s, credited) = credit_session(&env, &owner, promo.secs);
+        record_granted(&env, GrantSource::Comp, credited);
+        publish(&env, (symbol_short!("redeem"), owner), s.remaining_secs);
+    }
+
+    /// Indica se a ordem pode ser creditada agora (existe, está pendente e o pacote
+    /// ainda existe). Somente leitura; nunca entra em pânico.
+    pub fn can_grant(env: Env, owner: Address, order_id: u128) -> bool {
+        check_grantable(&env, &owner, order_id).is_ok()
+    }
+
+    /// Registro bruto da ordem, ou `None` se não existe; nunca entra em pânico.
+    pub fn try_get_order(env: Env, owner: Address, order_id: u128) -> Option<OrderRec> {
+        load_order(&env, &owner, order_id)
+    }
+
+    /// Segundos da ordem ainda não creditados (`duration_secs - credited_secs`);
+    /// 0 se não há mais o que creditar (creditada, reembolsada ou sem pacote).
+    /// Não depende de a ordem poder ser creditada agora (pausa, bloqueio,
+    /// GrantDelaySecs, StrictGrant).
+    pub fn order_remaining(env: Env, owner: Address, order_id: u128) -> u64 {
+        let ord = load_order(&env, &owner, order_id)
+            .unwrap_or_else(|| panic_with_error!(&env, Error::OrderNotFound));
+        if ord.credited || ord.refunded {
+            return 0;
+        }
+        env.storage()
+            .instance()
+            .get::<_, Package>(&DataKey::Package(ord.package_id))
+            .map_or(0, |pkg| {
+                (pkg.duration_secs as u64).saturating_sub(ord.credited_secs)
+            })
+    }
+
+    /// Primeira ordem pendente (menor id, não creditada nem reembolsada) do owner.
+    pub fn first_pending_order(env: Env, owner: Address) -> Option<(u128, OrderRec)> {
+        let last = last_order_id(&env, &owner);
+        let mut id: u128 = 1;
+        while id <= last {
+            if let Some(ord) = load_order(&env, &owner, id) {
+                if !ord.credited && !ord.refunded {
+                    return Some((id, ord));
+                }
+            }
+            id += 1;
+        }
+        None
+    }
+
+    /// Próxima ordem creditável do owner já com os dados do pacote:
+    /// `(order_id, package_id, duration_secs, price)`. Ordens cujo pacote foi
+    /// removido são puladas, já que o grant delas falharia.
+    pub fn next_grantable(env: Env, owner: Address) -> Option<(u128, u32, u32, i128)> {
+        let last = last_order_id(&env, &owner);
+        let mut id: u128 = 1;
+        while id <= last {
+            if let Some(ord) = load_order(&env, &owner, id) {
+                if !ord.credited && !ord.refunded {
+                    let pkg: Option<Package> = env
+                        .storage()
+                        .instance()
+                        .get(&DataKey::Package(ord.package_id));
+                    if let Some(pkg) = pkg {
+                        return Some((id, ord.package_id, pkg.duration_secs, pkg.price));
+                    }
+                }
+            }
+            id += 1;
+        }
+        None
+    }
+
+    /// Ordens do owner no estado `status`, a partir do id `start`, com no máximo
+    /// `limit` resultados (o filtro é aplicado durante a varredura).
+    pub fn orders_by_status(
+        env: Env,
+        owner: Address,
+        status: OrderStatus,
+        start: u128,
+        limit: u32,
+    ) -> Vec<(u128, OrderRec)> {
+        let last = last_order_id(&env, &owner);
+        let mut out = Vec::new(&env);
+        let mut id = start.max(1);
+        while id <= last && out.len() < limit {
+            if let Some(ord) = load_order(&env, &owner, id) {
+                if order_status(&ord) == status {
+                    out.push_back((id, ord));
+                }
+            }
+            id += 1;
+        }
+        out
+    }
+
+    /// Só os ids das ordens pendentes do owner, a partir de `start`, com no máximo
+    /// `limit` resultados (versão enxuta de `orders_by_status` para listas).
+    pub fn open_order_ids(env: Env, owner: Address, start: u128, limit: u32) -> Vec<u128> {
+        let last = last_order_id(&env, &owner);
+        let mut out = Vec::new(&env);
+        let mut id = start.max(1);
+        while id <= last && out.len() < limit {
+            if let Some(ord) = load_order(&env, &owner, id) {
+                if order_status(&ord) == OrderStatus::Pending {
+                    out.push_back(id);
+                }
+            }
+            id += 1;
+        }
+        out
+    }
+
+    /// Cancela uma ordem ainda não creditada e devolve o valor retido em escrow.
+    /// Ordens pagas direto à tesouraria não são reembolsáveis por aqui.
+    pub fn cancel_order(env: Env, owner: Address, order_id: u128) {
+        owner.require_auth();
+        let mut ord = load_order(&env, &owner, order_id)
+            .unwrap_or_else(|| panic_with_error!(&env, Error::OrderNotFound));
+        if ord.credited {
+            panic_with_error!(&env, Error::AlreadyGranted);
+        }
+        // já creditada em parte: o owner ficaria com o tempo e o dinheiro
+        if ord.refunded || !ord.escrowed || ord.credited_secs > 0 {
+            panic_with_error!(&env, Error::InvalidOrderState);
+        }
+
+        refund_order(&env, &owner, order_id, &mut ord);
+        publish(
+            &env,
+            (symbol_short!("cancel"), owner, order_id),
+            order_held(&ord),
+        );
+    }
+
+    /// Janela após a qual ordens pendentes podem ser reembolsadas por
+    /// `sweep_refunds` (admin). Zero desliga o sweep.
+    pub fn set_refund_window(env: Env, secs: u64) {
+        require_admin(&env);
+        let old: u64 = env
+            .storage()
+            .instance()
+            .get(&DataKey::RefundWindowSecs)
+            .unwrap_or(0);
+        env.storage()
+            .instance()
+            .set(&DataKey::RefundWindowSecs, &secs);
+        publish_cfg(&env, "refund_window", old, secs);
+    }
+
+    /// Keeper: reembolsa as ordens pendentes do owner em `[start, start + limit)`
+    /// mais velhas que a janela de reembolso e devolve quantas foram reembolsadas.
+    /// Ordens já creditadas em parte (`grant_partial`) ficam de fora, assim como
+    /// as legadas (`LegacyOrderRec`), sem data de compra.
+    /// Ordens em escrow saem do contrato; as pagas direto saem da tesouraria,
+    /// que precisa assinar.
+    pub fn sweep_refunds(env: Env, owner: Address, start: u128, limit: u32) -> u32 {
+        let window: u64 = env
+            .storage()
+            .instance()
+            .get(&DataKey::RefundWindowSecs)
+            .unwrap_or(0);
+        if window == 0 {
+            return 0;
+        }
+        let now = env.ledger().timestamp();
+        let end = start
+            .saturating_add(limit as u128)
+            .min(last_order_id(&env, &owner).saturating_add(1));
+
+        let mut refunded = 0u32;
+        let mut id = start.max(1);
+        while id < end {
+            if let Some(mut ord) = load_order(&env, &owner, id) {
+                if !ord.credited
+                    && !ord.refunded
+                    && ord.credited_secs == 0
+                    && ord.created_at > 0
+                    && now.saturating_sub(ord.created_at) >= window
+                {
+                    refund_order(&env, &owner, id, &mut ord);
+                    refunded += 1;
+                }
+            }
+            id += 1;
+        }
+        refunded
+    }
+
+    // -------------------- sessão de grupo (plano de equipe) -----------------
+    /// Cria o grupo `group` com `owner` como dono e primeiro membro.
+    pub fn create_group(env: Env, owner: Address, group: Symbol) {
+        owner.require_auth();
+        let store = env.storage().persistent();
+        if store.has(&DataKey::GroupOwner(group.clone())) {
+            panic_with_error!(&env, Error::Unauthorized);
+        }
+        store.set(&DataKey::GroupOwner(group.clone()), &owner);
+        store.set(&DataKey::GroupMember(group.clone(), owner.clone()), &true);
+        publish(&env, (symbol_short!("g_new"), group), owner);
+    }
+
+    /// Adiciona um membro ao grupo (dono do grupo ou admin).
+    pub fn add_member(env: Env, caller: Address, group: Symbol, member: Address) {
+        require_group_manager(&env, &group, &caller);
+        env.storage()
+            .persistent()
+            .set(&DataKey::GroupMember(group.clone(), member.clone()), &true);
+        publish(&env, (symbol_short!("g_add"), group), member);
+    }
+
+    /// Remove um membro do grupo (dono do grupo ou admin).
+    pub fn remove_member(env: Env, caller: Address, group: Symbol, member: Address) {
+        require_group_manager(&env, &group, &caller);
+        env.storage()
+            .persistent()
+            .remove(&DataKey::GroupMember(group.clone(), member.clone()));
+        publish(&env, (symbol_short!("g_rm"), group), member);
+    }
+
+    pub fn is_member(env: Env, group: Symbol, member: Address) -> bool {
+        is_group_member(&env, &group, &member)
+    }
+
+    /// Credita a ordem de `owner` no saldo compartilhado do grupo; `owner`
+    /// precisa ser membro. Autorização igual à do `grant`.
+    pub fn grant_to_group(
+        env: Env,
+        caller: Address,
+        owner: Address,
+        order_id: u128,
+        group: Symbol,
+    ) {
+        authorize_grant(&env, &caller, &owner);
+        group_owner(&env, &group);
+        if !is_group_member(&env, &group, &owner) {
+            panic_with_error!(&env, Error::Unauthorized);
+        }
+        let s = credit_order_into(
+            &env,
+            &owner,
+            order_id,
+            None,
+            &DataKey::GroupSession(group.clone()),
+            None,
+        );
+        publish(
+            &env,
+            (symbol_short!("g_grant"), group, owner),
+            s.remaining_secs,
+        );
+    }
+
+    /// Inicia o consumo do saldo do grupo (qualquer membro).
+    pub fn start_group(env: Env, member: Address, group: Symbol) {
+        require_member(&env, &group, &member);
+        ensure_can_operate(&env, &member);
+        let key = DataKey::GroupSession(group.clone());
+        let mut s = load_session_at(&env, &key);
+        if s.started_at > 0 || s.remaining_secs == 0 {
+            return;
+        }
+        let now = env.ledger().timestamp();
+        s.started_at = now;
+        save_session_at(&env, &key, None, &s);
+        publish(
+            &env,
+            (symbol_short!("g_start"), group, member),
+            (now, now.saturating_add(s.remaining_secs)),
+        );
+    }
+
+    /// Pausa o consumo do saldo do grupo (qualquer membro).
+    pub fn pause_group(env: Env, member: Address, group: Symbol) {
+        require_member(&env, &group, &member);
+        let key = DataKey::GroupSession(group.clone());
+        let mut s = load_session_at(&env, &key);
+        if s.started_at == 0 {
+            return;
+        }
+        settle_at(&env, &mut s, env.ledger().timestamp());
+        s.started_at = 0;
+        save_session_at(&env, &key, None, &s);
+        publish(
+            &env,
+            (symbol_short!("g_pause"), group, member),
+            s.remaining_secs,
+        );
+    }
+
+    pub fn get_group_session(env: Env, group: Symbol) -> Session {
+        load_session_at(&env, &DataKey::GroupSession(group))
+    }
+
+    // -------------------- sessões nomeadas (dispositivos) --------------------
+    /// Move `secs` do saldo principal do owner para o slot `name` (criando-o),
+    /// para consumir em outro dispositivo. Saldo agendado (`not_before` futuro)
+    /// ou expirado por inatividade não pode ser movido.
+    pub fn fund_session(env: Env, owner: Address, name: Symbol, secs: u64) {
+        owner.require_auth();
+        ensure_can_operate(&env, &owner);
+        if secs == 0 {
+            panic_with_error!(&env, Error::InvalidAmount);
+        }
+        let now = env.ledger().timestamp();
+        let mut s = load_session(&env, &owner);
+        if now < s.not_before {
+            panic_with_error!(&env, Error::NotYetAvailable);
+        }
+        if idle_expired(&env, &s, now) {
+            panic_with_error!(&env, Error::SessionIdleExpired);
+        }
+        settle_at(&env, &mut s, now);
+        // com a execução limitada pelo teto diário, sai primeiro do saldo retido
+        let main = DataKey::Session(owner.clone());
+        let held = cap_hold(&env, &main).unwrap_or(0);
+        if s.remaining_secs.saturating_add(held) < secs {
+            panic_with_error!(&env, Error::InsufficientBalance);
+        }
+        let from_held = held.min(secs);
+        if from_held > 0 {
+            env.storage()
+                .persistent()
+                .set(&cap_hold_key(&env, &main), &(held - from_held));
+        }
+        s.remaining_secs -= secs - from_held;
+        if s.remaining_secs == 0 {
+            s.started_at = 0;
+        }
+        save_session(&env, &owner, &s);
+
+        let key = DataKeyExt::NamedSession(owner.clone(), name.clone());
+        let (named, _) = credit_session_at(&env, &key, &owner, Some(&owner), secs);
+        register_session_name(&env, &owner, &name);
+        publish(
+            &env,
+            (symbol_short!("s_fund"), owner, name),
+            (secs, named.remaining_secs),
+        );
+    }
 
-// -------------------------------------------------------------
-// EVENTOS
-// -------------------------------------------------------------
-// fn emit_purchase_created(env: &Env, owner: &Address, package_id: u32, order_id: u128, price: i128) {
-//     env.events().publish(
-//         (symbol_short!("purchase"), symbol_short!("created")),
-//         (owner, package_id, order_id, price),
-//     );
-// }
-fn emit_grant(env: &Env, owner: &Address, order_id: u128, new_remaining: u64) {
-    env.events()
-        .publish((symbol_short!("grant"), owner, order_id), new_remaining);
-}
+    /// `grant` creditando o tempo da ordem no slot `session`; `"default"` é a
+    /// sessão principal (a mesma do `grant`).
+    pub fn grant_to_session(
+        env: Env,
+        caller: Address,
+        owner: Address,
+        order_id: u128,
+        session: Symbol,
+    ) {
+        authorize_grant(&env, &caller, &owner);
+        if session == symbol_short!("default") {
+            apply_grant(&env, &owner, order_id);
+            return;
+        }
+        let key = DataKeyExt::NamedSession(owner.clone(), session.clone());
+        credit_order_into(&env, &owner, order_id, None, &key, Some(&owner));
+        register_session_name(&env, &owner, &session);
+    }
 
-// -------------------------------------------------------------
-// CONTRATO
-// -------------------------------------------------------------
-#[contract]
-pub struct AccessTime;
+    /// Inicia o consumo do slot `name` (owner ou delegado).
+    pub fn start_named(env: Env, caller: Address, owner: Address, name: Symbol) {
+        authorize_session(&env, &caller, &owner);
+        ensure_can_operate(&env, &owner);
+        let key = DataKeyExt::NamedSession(owner.clone(), name.clone());
+        let mut s = load_session_at(&env, &key);
+        let now = env.ledger().timestamp();
+        observe_named_expiry(&env, &owner, &name, &s, now);
+        stop_capped_run(&env, &key, &owner, &mut s, now);
+        if s.started_at > 0 || s.remaining_secs == 0 {
+            return;
+        }
+        ensure_startable(&env, &key, &owner, &mut s, now);
+        s.started_at = now;
+        save_session_at(&env, &key, Some(&owner), &s);
+        publish(
+            &env,
+            (symbol_short!("s_start"), owner, name),
+            (now, now.saturating_add(s.remaining_secs)),
+        );
+    }
 
-#[contractimpl]
-impl AccessTime {
-    // -------------------- init / set_package (inalterados) --------------------
-    pub fn init(env: Env, admin: Address, xml_asset: Address) {
-        if env.storage().instance().has(&DataKey::Admin) {
-            panic_with_error!(&env, Error::AlreadyInitialized);
+    /// Pausa o consumo do slot `name` (owner ou delegado).
+    pub fn pause_named(env: Env, caller: Address, owner: Address, name: Symbol) {
+        authorize_session(&env, &caller, &owner);
+        let key = DataKeyExt::NamedSession(owner.clone(), name.clone());
+        let mut s = load_session_at(&env, &key);
+        let now = env.ledger().timestamp();
+        observe_named_expiry(&env, &owner, &name, &s, now);
+        if s.started_at == 0 {
+            return;
         }
-        env.storage().instance().set(&DataKey::Admin, &admin);
-        env.storage().instance().set(&DataKey::Token, &xml_asset);
-        env.events()
-            .publish((symbol_short!("init"), admin.clone()), xml_asset);
+        settle_at(&env, &mut s, now);
+        s.started_at = 0;
+        save_session_at(&env, &key, Some(&owner), &s);
+        publish(
+            &env,
+            (symbol_short!("s_pause"), owner, name),
+            s.remaining_secs,
+        );
     }
 
-    pub fn set_package(env: Env, id: u32, price: i128, duration_secs: u32) {
-        let admin: Address = env
+    /// Slots nomeados do owner com o estado gravado de cada um.
+    pub fn list_sessions(env: Env, owner: Address) -> Vec<(Symbol, Session)> {
+        let names: Vec<Symbol> = env
             .storage()
-            .instance()
-            .get(&DataKey::Admin)
-            .ok_or(Error::NotInitialized)
-            .unwrap();
-        admin.require_auth();
-        let pkg = Package {
-            price,
-            duration_secs,
-        };
-        env.storage().instance().set(&DataKey::Package(id), &pkg);
-        env.events()
-            .publish((symbol_short!("pkg_set"), id), (price, duration_secs));
+            .persistent()
+            .get(&DataKeyExt::SessionNames(owner.clone()))
+            .unwrap_or(Vec::new(&env));
+        let mut out = Vec::new(&env);
+        for name in names.iter() {
+            let s = load_session_at(&env, &DataKeyExt::NamedSession(owner.clone(), name.clone()));
+            out.push_back((name, s));
+        }
+        out
     }
 
-    // Add this function to the contract implementation
-    pub fn get_package(env: Env, package_id: u32) -> Package {
+    // -------------------- passe transferível ---------------------------------
+    /// Converte todo o saldo da sessão do owner num `TimePass` transferível e
+    /// zera a sessão (consolidando antes, se estiver rodando). Devolve o pass_id.
+    pub fn mint_pass(env: Env, owner: Address) -> u128 {
+        owner.require_auth();
+        ensure_can_operate(&env, &owner);
+        let mut s = load_session(&env, &owner);
+        settle_at(&env, &mut s, env.ledger().timestamp());
+        let held = take_cap_hold(&env, &DataKey::Session(owner.clone()));
+        let secs = s.remaining_secs.saturating_add(held);
+        if secs == 0 {
+            panic_with_error!(&env, Error::InvalidAmount);
+        }
+        s.remaining_secs = 0;
+        s.started_at = 0;
+        save_session(&env, &owner, &s);
+
+        let inst = env.storage().instance();
+        let pass_id: u128 = inst.get(&DataKeyExt::NextTimePass).unwrap_or(0) + 1;
+        inst.set(&DataKeyExt::NextTimePass, &pass_id);
+        let pass = TimePass {
+            secs,
+            holder: owner.clone(),
+        };
         env.storage()
-            .instance()
-            .get(&DataKey::Package(package_id))
-            .unwrap_or_else(|| panic_with_error!(&env, Error::PackageNotFound))
+            .persistent()
+            .set(&DataKeyExt::TimePass(pass_id), &pass);
+        publish(&env, (symbol_short!("p_mint"), owner, pass_id), secs);
+        pass_id
     }
-    pub fn get_admin(env: Env) -> Address {
+
+    pub fn get_time_pass(env: Env, pass_id: u128) -> Option<TimePass> {
         env.storage()
-            .instance()
-            .get(&DataKey::Admin)
-            .unwrap_or_else(|| panic_with_error!(&env, Error::NotInitialized))
+            .persistent()
+            .get(&DataKeyExt::TimePass(pass_id))
     }
 
-    pub fn get_token(env: Env) -> Address {
+    /// Transfere o passe para `to`; só o detentor atual pode transferir.
+    pub fn transfer_pass(env: Env, from: Address, to: Address, pass_id: u128) {
+        from.require_auth();
+        ensure_can_operate(&env, &from);
+        ensure_can_operate(&env, &to);
+        let mut pass = load_time_pass(&env, pass_id);
+        if pass.holder != from {
+            panic_with_error!(&env, Error::Unauthorized);
+        }
+        pass.holder = to.clone();
         env.storage()
-            .instance()
-            .get(&DataKey::Token)
-            .unwrap_or_else(|| panic_with_error!(&env, Error::NotInitialized))
+            .persistent()
+            .set(&DataKeyExt::TimePass(pass_id), &pass);
+        publish(&env, (symbol_short!("p_xfer"), from, pass_id), to);
     }
 
-    // -------------------- NOVO: buy_order (compra sem crédito) ----------------
-
-    fn dbg(env: &Env, step: &str) {
-        env.events()
-            .publish((Symbol::new(env, "dbg"), Symbol::new(env, step)), ());
+    /// Resgata o passe: credita os segundos na sessão do detentor (respeitando
+    /// MaxSessionSecs) e queima o passe. Devolve o novo saldo.
+    pub fn redeem_pass(env: Env, holder: Address, pass_id: u128) -> u64 {
+        holder.require_auth();
+        ensure_can_operate(&env, &holder);
+        let pass = load_time_pass(&env, pass_id);
+        if pass.holder != holder {
+            panic_with_error!(&env, Error::Unauthorized);
+        }
+        env.storage()
+            .persistent()
+            .remove(&DataKeyExt::TimePass(pass_id));
+        let (s, credited) = credit_session(&env, &holder, pass.secs);
+        publish(&env, (symbol_short!("p_redeem"), holder, pass_id), credited);
+        s.remaining_secs
     }
 
-    pub fn buy_order(env: Env, owner: Address, package_id: u32) -> u128 {
+    // -------------------- passe absoluto ------------------------------------
+    /// Compra um passe de expiração absoluta: o acesso vale de agora até
+    /// `agora + duration_secs` e não pode ser pausado. Um passe por owner.
+    pub fn buy_pass(env: Env, owner: Address, package_id: u32) -> u64 {
         owner.require_auth();
-        Self::dbg(&env, "start");
-
-        let admin: Address = env
-            .storage()
-            .instance()
-            .get(&DataKey::Admin)
-            .unwrap_or_else(|| {
-                Self::dbg(&env, "err_no_admin");
-                panic_with_error!(&env, Error::NotInitialized)
-            });
-
-        let token_id: Address = env
-            .storage()
-            .instance()
-            .get(&DataKey::Token)
-            .unwrap_or_else(|| {
-                Self::dbg(&env, "err_no_token");
-                panic_with_error!(&env, Error::NotInitialized)
-            });
-
+        ensure_can_operate(&env, &owner);
         let pkg: Package = env
             .storage()
             .instance()
             .get(&DataKey::Package(package_id))
-            .unwrap_or_else(|| {
-                Self::dbg(&env, "err_pkg_nf");
-                panic_with_error!(&env, Error::PackageNotFound)
-            });
+            .unwrap_or_else(|| panic_with_error!(&env, Error::PackageNotFound));
+        check_on_sale(&env, package_id).unwrap_or_else(|e| panic_with_error!(&env, e));
 
-        Self::dbg(&env, "before_transfer");
-        let token = TokenClient::new(&env, &token_id);
-        token.transfer(&owner, &admin, &pkg.price); // se der erro do SAC, diagnostics mostram
-
-        Self::dbg(&env, "after_transfer");
+        let now = env.ledger().timestamp();
+        let key = DataKey::Pass(owner.clone());
+        if let Some(p) = env.storage().persistent().get::<_, Pass>(&key) {
+            if p.expires_at > now {
+                panic_with_error!(&env, Error::InvalidOrderState);
+            }
+        }
 
-        // >>>>> ALTERAÇÃO: gerar order_id determinístico pelo contador <<<<<
-        let order_id: u128 = next_order_id(&env, &owner);
+        let price = quote_price(&env, &owner, pkg.price);
+        let token = TokenClient::new(&env, &load_token(&env));
+        pay_out(&env, &token, &owner, price);
+        consume_stock(&env, package_id);
 
-        save_order(
+        let pass = Pass {
+            package_id,
+            expires_at: now + pkg.duration_secs as u64,
+            total_secs: pkg.duration_secs as u64,
+            price_paid: price,
+        };
+        env.storage().persistent().set(&key, &pass);
+        let until: u64 = env
+            .storage()
+            .instance()
+            .get(&DataKeyExt::PassesLiveUntil)
+            .unwrap_or(0);
+        env.storage()
+            .instance()
+            .set(&DataKeyExt::PassesLiveUntil, &until.max(pass.expires_at));
+        publish(
             &env,
-            &owner,
-            order_id,
-            &OrderRec {
-                package_id,
-                credited: false,
-            },
+            (symbol_short!("pass"), owner),
+            (package_id, pass.expires_at),
         );
+        pass.expires_at
+    }
 
-        env.events().publish(
-            (Symbol::new(&env, "purchase"), Symbol::new(&env, "created")),
-            (owner, package_id, order_id, pkg.price),
-        );
-        Self::dbg(&env, "done");
-        order_id
+    pub fn get_pass(env: Env, owner: Address) -> Option<Pass> {
+        env.storage().persistent().get(&DataKey::Pass(owner))
     }
 
-    // -------------------- NOVO: grant (owner OU admin) ------------------------
-    /// Credita os segundos do pacote na sessão do `owner` referentes à `order_id`.
-    /// Pode ser chamado pelo **owner** (self-serve) OU pelo **admin**.
-    /// - Idempotente: se já creditado, retorna erro `AlreadyGranted`.
-    pub fn grant(env: Env, caller: Address, owner: Address, order_id: u128) {
-        // autoriza: caller deve ser admin OU o próprio owner
-        let admin: Address = env
+    /// Encerra o passe do owner e devolve a parte proporcional ao tempo ainda
+    /// não usado: `price_paid * restante / total`. O valor sai da tesouraria,
+    /// que precisa assinar. Devolve o montante reembolsado.
+    pub fn refund_unused(env: Env, owner: Address) -> i128 {
+        owner.require_auth();
+        let key = DataKey::Pass(owner.clone());
+        let pass: Pass = env
             .storage()
-            .instance()
-            .get(&DataKey::Admin)
-            .ok_or(Error::NotInitialized)
-            .unwrap();
-        if caller != admin && caller != owner {
-            panic_with_error!(&env, Error::Unauthorized);
+            .persistent()
+            .get(&key)
+            .unwrap_or_else(|| panic_with_error!(&env, Error::OrderNotFound));
+        let left = pass.expires_at.saturating_sub(env.ledger().timestamp());
+        let amount = if pass.total_secs == 0 {
+            0
+        } else {
+            pass.price_paid * left as i128 / pass.total_secs as i128
+        };
+        env.storage().persistent().remove(&key);
+
+        if amount > 0 {
+            let treasury = treasury(&env);
+            treasury.require_auth();
+            TokenClient::new(&env, &load_token(&env)).transfer(&treasury, &owner, &amount);
         }
-        caller.require_auth();
+        publish(&env, (symbol_short!("pass_ref"), owner), (left, amount));
+        amount
+    }
 
-        // busca ordem; precisa existir e não ter sido creditada
-        let mut ord = load_order(&env, &owner, order_id)
-            .ok_or(Error::OrderNotFound)
-            .unwrap();
-        if ord.credited {
-            panic_with_error!(&env, Error::AlreadyGranted);
+    // -------------------- sessão medida (pay-as-you-go) ---------------------
+    /// Configura o modo medido (admin): `per_second_price` cobrado por segundo
+    /// consumido e `min_secs` que o allowance do usuário precisa cobrir no start.
+    pub fn set_metered_price(env: Env, per_second_price: i128, min_secs: u64) {
+        require_admin(&env);
+        if per_second_price < 0 {
+            panic_with_error!(&env, Error::InvalidAmount);
         }
+        let inst = env.storage().instance();
+        let old: (i128, u64) = (
+            inst.get(&DataKey::PerSecondPrice).unwrap_or(0),
+            inst.get(&DataKey::MeteredMinSecs).unwrap_or(0),
+        );
+        inst.set(&DataKey::PerSecondPrice, &per_second_price);
+        inst.set(&DataKey::MeteredMinSecs, &min_secs);
+        publish_cfg(&env, "metered_price", old, (per_second_price, min_secs));
+    }
 
-        // pega pacote vinculado à ordem
-        let pkg: Package = env
+    /// Inicia uma sessão medida: nada é pré-pago; o `pause` cobra o tempo usado
+    /// via allowance dado ao contrato. Exige allowance e saldo para o mínimo.
+    pub fn start_metered(env: Env, owner: Address) {
+        owner.require_auth();
+        ensure_can_operate(&env, &owner);
+        let price = per_second_price(&env);
+        if price == 0 {
+            panic_with_error!(&env, Error::InvalidFee);
+        }
+        let key = DataKey::MeteredStart(owner.clone());
+        if env.storage().persistent().has(&key) {
+            return;
+        }
+
+        let min_secs: u64 = env
             .storage()
             .instance()
-            .get(&DataKey::Package(ord.package_id))
-            .ok_or(Error::PackageNotFound)
-            .unwrap();
+            .get(&DataKey::MeteredMinSecs)
+            .unwrap_or(0);
+        let min_charge = min_secs as i128 * price;
+        let token = TokenClient::new(&env, &load_token(&env));
+        if token.allowance(&owner, &env.current_contract_address()) < min_charge
+            || token.balance(&owner) < min_charge
+        {
+            panic_with_error!(&env, Error::InsufficientBalance);
+        }
 
-        // credita tempo (não inicia)
-        let mut s = load_session(&env, &owner);
-        s.remaining_secs = s.remaining_secs.saturating_add(pkg.duration_secs as u64);
-        save_session(&env, &owner, &s);
+        let now = env.ledger().timestamp();
+        env.storage().persistent().set(&key, &now);
+        let open: u32 = env
+            .storage()
+            .instance()
+            .get(&DataKeyExt::OpenMetered)
+            .unwrap_or(0);
+        env.storage()
+            .instance()
+            .set(&DataKeyExt::OpenMetered, &(open + 1));
+        env.storage().instance().set(
+            &DataKey::ActiveCount,
+            &active_count_of(&env).saturating_add(1),
+        );
+        publish(&env, (symbol_short!("m_start"), owner), now);
+    }
 
-        // marca como creditado e emite evento
-        ord.credited = true;
-        save_order(&env, &owner, order_id, &ord);
-        emit_grant(&env, &owner, order_id, s.remaining_secs);
+    pub fn is_metered(env: Env, owner: Address) -> bool {
+        env.storage()
+            .persistent()
+            .has(&DataKey::MeteredStart(owner))
     }
 
     // -------------------- start / pause / getters (inalterados) ---------------
     pub fn start(env: Env, owner: Address) {
+        Self::start_by(env, owner.clone(), owner);
+    }
+
+    pub fn pause(env: Env, owner: Address) {
+        Self::pause_by(env, owner.clone(), owner);
+    }
+
+    /// Define (ou limpa, com `None`) o delegado do owner: ele pode dar `start_by`
+    /// e `pause_by` na sessão, mas não compra nem credita ordens.
+    pub fn set_delegate(env: Env, owner: Address, delegate: Option<Address>) {
         owner.require_auth();
-        let now = env.ledger().timestamp();
-        let mut s = load_session(&env, &owner);
-        if remaining_at(&env, &s, now) == 0 {
-            return;
+        let key = DataKey::Delegate(owner.clone());
+        match &delegate {
+            Some(d) => env.storage().persistent().set(&key, d),
+            None => env.storage().persistent().remove(&key),
         }
-        if s.started_at == 0 {
-            s.started_at = now;
-            save_session(&env, &owner, &s);
-            env.events().publish((symbol_short!("start"), owner), now);
+        publish(&env, (symbol_short!("delegate"), owner), delegate);
+    }
+
+    pub fn get_delegate(env: Env, owner: Address) -> Option<Address> {
+        env.storage().persistent().get(&DataKey::Delegate(owner))
+    }
+
+    /// `start` chamado pelo owner ou pelo seu delegado.
+    pub fn start_by(env: Env, caller: Address, owner: Address) {
+        authorize_session(&env, &caller, &owner);
+        start_session(&env, &owner);
+    }
+
+    /// `pause` chamado pelo owner ou pelo seu delegado.
+    pub fn pause_by(env: Env, caller: Address, owner: Address) {
+        authorize_session(&env, &caller, &owner);
+        if let Some(started) = metered_started_at(&env, &owner) {
+            ensure_min_active(&env, started, env.ledger().timestamp());
         }
+        settle_metered(&env, &owner);
+        pause_session(&env, &owner);
     }
 
-    pub fn pause(env: Env, owner: Address) {
-        owner.require_auth();
-        let now = env.ledger().timestamp();
+    /// Total de segundos efetivamente consumidos pelo owner (sessão principal e
+    /// slots nomeados), somado a cada consolidação: pause, settle, crédito com a
+    /// sessão rodando e expiração gravada. A execução em aberto não entra.
+    pub fn consumed_secs(env: Env, owner: Address) -> u64 {
+        env.storage()
+            .persistent()
+            .get(&DataKeyExt::ConsumedSecs(owner))
+            .unwrap_or(0)
+    }
+
+    /// Total de segundos que a sessão passou pausada entre um `pause` e o `start`
+    /// seguinte (inclui o intervalo em aberto, se estiver pausada agora).
+    pub fn paused_secs(env: Env, owner: Address) -> u64 {
+        let total: u64 = env
+            .storage()
+            .persistent()
+            .get(&DataKey::TotalPausedSecs(owner.clone()))
+            .unwrap_or(0);
+        let open: u64 = env
+            .storage()
+            .persistent()
+            .get::<_, u64>(&DataKey::PausedAt(owner))
+            .map(|paused_at| env.ledger().timestamp().saturating_sub(paused_at))
+            .unwrap_or(0);
+        total.saturating_add(open)
+    }
+
+    /// Regrava a `Session` do owner no layout atual (qualquer um pode chamar).
+    /// Entradas v1 (`LegacySession`) e v2 (`SessionV2`) ganham os campos novos
+    /// com valores padrão (`last_activity` = agora, para não confiscar por
+    /// inatividade de imediato); entradas já atuais só recebem o marcador.
+    pub fn migrate_session(env: Env, owner: Address) {
+        let key = DataKey::Session(owner.clone());
+        let Some(raw) = env.storage().persistent().get::<_, Map<Symbol, Val>>(&key) else {
+            return;
+        };
+        // structs são gravadas como mapa campo -> valor; v1 não tem `not_before`
+        // e v2 não tem `last_activity`
+        if !raw.contains_key(Symbol::new(&env, "last_activity")) {
+            let (remaining_secs, started_at, not_before) =
+                if raw.contains_key(Symbol::new(&env, "not_before")) {
+                    let v2 = SessionV2::try_from_val(&env, &raw.to_val())
+                        .unwrap_or_else(|_| panic_with_error!(&env, Error::SchemaMismatch));
+                    (v2.remaining_secs, v2.started_at, v2.not_before)
+                } else {
+                    let v1 = LegacySession::try_from_val(&env, &raw.to_val())
+                        .unwrap_or_else(|_| panic_with_error!(&env, Error::SchemaMismatch));
+                    (v1.remaining_secs, v1.started_at, 0)
+                };
+            let s = Session {
+                remaining_secs,
+                started_at,
+                not_before,
+                last_activity: env.ledger().timestamp(),
+            };
+            // grava direto: save_session leria o layout antigo para o ActiveCount
+            env.storage().persistent().set(&key, &s);
+            publish(
+                &env,
+                (symbol_short!("migrated"), owner.clone()),
+                SESSION_SCHEMA_VERSION,
+            );
+        }
+        env.storage()
+            .persistent()
+            .set(&DataKey::SessionSchema(owner), &SESSION_SCHEMA_VERSION);
+    }
+
+    pub fn session_schema(env: Env, owner: Address) -> u32 {
+        // sem marcador: sessão criada já no layout atual ou inexistente
+        env.storage()
+            .persistent()
+            .get(&DataKey::SessionSchema(owner))
+            .unwrap_or(SESSION_SCHEMA_VERSION)
+    }
+
+    /// Tempo máximo que uma sessão pode ficar pausada com saldo (admin); além
+    /// disso `start` é recusado e o saldo pode ser confiscado. 0 desliga.
+    pub fn set_max_idle_secs(env: Env, secs: u64) {
+        require_admin(&env);
+        let old: u64 = env
+            .storage()
+            .instance()
+            .get(&DataKeyExt::MaxIdleSecs)
+            .unwrap_or(0);
+        env.storage()
+            .instance()
+            .set(&DataKeyExt::MaxIdleSecs, &secs);
+        publish_cfg(&env, "max_idle_secs", old, secs);
+    }
+
+    /// Zera o saldo de uma sessão pausada além de MaxIdleSecs (qualquer um,
+    /// ex.: keeper). Devolve os segundos confiscados; 0 se ainda não expirou.
+    pub fn forfeit(env: Env, owner: Address) -> u64 {
         let mut s = load_session(&env, &owner);
-        if s.started_at > 0 {
-            let gasto = now.saturating_sub(s.started_at);
-            s.remaining_secs = s.remaining_secs.saturating_sub(gasto);
-            s.started_at = 0;
-            save_session(&env, &owner, &s);
-            env.events()
-                .publish((symbol_short!("pause"), owner), s.remaining_secs);
+        if !idle_expired(&env, &s, env.ledger().timestamp()) {
+            return 0;
         }
+        let secs = s.remaining_secs;
+        s.remaining_secs = 0;
+        save_session(&env, &owner, &s);
+        publish(&env, (symbol_short!("forfeit"), owner), secs);
+        secs
+    }
+
+    /// Liga/desliga a expiração "ansiosa" (admin): com ela, `get_session`,
+    /// `is_active_now` e `remaining_now` gravam o fim de uma sessão expirada.
+    pub fn set_auto_settle(env: Env, enabled: bool) {
+        require_admin(&env);
+        let old: bool = env
+            .storage()
+            .instance()
+            .get(&DataKeyExt::AutoSettle)
+            .unwrap_or(false);
+        env.storage()
+            .instance()
+            .set(&DataKeyExt::AutoSettle, &enabled);
+        publish_cfg(&env, "auto_settle", old, enabled);
     }
 
     pub fn get_session(env: Env, owner: Address) -> Session {
+        auto_settle(&env, &owner, env.ledger().timestamp());
         load_session(&env, &owner)
     }
 
+    /// Folga global em segundos somada ao fim das sessões que já estavam
+    /// rodando agora (admin), p/ compensar uma queda sem varrer usuários. Cada
+    /// sessão recebe a folga uma vez: ao consolidar (pause, settle, crédito) ela
+    /// entra no saldo; starts posteriores não a recebem. Um novo valor substitui
+    /// o anterior para quem ainda não consolidou (0 retira).
+    pub fn set_global_grace(env: Env, secs: u64) {
+        require_admin(&env);
+        let (old, _) = grace_config(&env);
+        env.storage().instance().set(
+            &DataKeyExt::GlobalGraceOffset,
+            &(secs, env.ledger().timestamp()),
+        );
+        publish_cfg(&env, "global_grace", old, secs);
+    }
+
     pub fn get_access(env: Env, owner: Address) -> Access {
-        let s = load_session(&env, &owner);
-        let ea = if s.started_at > 0 {
-            s.started_at.saturating_add(s.remaining_secs)
-        } else {
-            0
-        };
+        let ea = access_ends_at(&env, &owner).unwrap_or(0);
         Access {
             owner,
             expires_at: ea,
         }
     }
 
+    /// Fim absoluto da sessão em andamento (`started_at + remaining_secs`, mais
+    /// a folga global); na sessão medida, até onde o allowance cobre. `None`
+    /// quando pausada.
+    pub fn ends_at(env: Env, owner: Address) -> Option<u64> {
+        access_ends_at(&env, &owner)
+    }
+
+    /// Fim projetado do acesso: para sessão rodando, o fim real
+    /// (`started_at + remaining_secs` + folga global); pausada, até quando
+    /// duraria se fosse retomada agora (`agora + remaining_secs`).
+    pub fn resume_preview(env: Env, owner: Address) -> u64 {
+        let s = load_session(&env, &owner);
+        if s.started_at > 0 {
+            return s
+                .started_at
+                .saturating_add(s.remaining_secs)
+                .saturating_add(grace_for(&env, &s));
+        }
+        env.ledger().timestamp().saturating_add(s.remaining_secs)
+    }
+
+    /// Sessão pré-paga rodando com saldo em `now` ou sessão medida em aberto.
     pub fn is_active(env: Env, owner: Address, now: u64) -> bool {
         let s = load_session(&env, &owner);
-        s.started_at > 0 && remaining_at(&env, &s, now) > 0
+        (s.started_at > 0 && remaining_at(&env, &s, now) > 0)
+            || metered_started_at(&env, &owner).is_some_and(|started| started <= now)
     }
 
     pub fn remaining(env: Env, owner: Address, now: u64) -> u64 {
         let s = load_session(&env, &owner);
         remaining_at(&env, &s, now)
     }
+
+    /// Saldo projetado em `at` se a sessão seguir no estado atual: rodando,
+    /// desconta o tempo até lá (0 se já teria acabado); pausada, o saldo
+    /// guardado. `at` no passado vale como agora.
+    pub fn remaining_at_ts(env: Env, owner: Address, at: u64) -> u64 {
+        let s = load_session(&env, &owner);
+        remaining_at(&env, &s, at.max(env.ledger().timestamp()))
+    }
+
+    /// `remaining_now` decomposto em (horas, minutos, segundos).
+    pub fn remaining_hms_now(env: Env, owner: Address) -> (u64, u64, u64) {
+        let secs = Self::remaining_now(env, owner);
+        (secs / 3600, secs % 3600 / 60, secs % 60)
+    }
+
+    /// Quantas sessões estão rodando, medidas em aberto incluídas. Aproximado: uma
+    /// sessão cujo saldo acabou continua contando até ser consolidada (pause,
+    /// grant, expiração pelo admin).
+    pub fn active_count(env: Env) -> u32 {
+        active_count_of(&env)
+    }
+
+    /// Diagnóstico em uma leitura: `(remaining_secs gravado, restante ao vivo
+    /// agora, started_at, ativa agora)`.
+    pub fn session_debug(env: Env, owner: Address) -> (u64, u64, u64, bool) {
+        let s = load_session(&env, &owner);
+        let live = remaining_at(&env, &s, env.ledger().timestamp());
+        (
+            s.remaining_secs,
+            live,
+            s.started_at,
+            s.started_at > 0 && live > 0,
+        )
+    }
+
+    /// Sessão rodando (`started_at > 0`), mesmo que o saldo já tenha acabado,
+    /// ou sessão medida em aberto; `is_active_now` exige também saldo restante.
+    pub fn is_running(env: Env, owner: Address) -> bool {
+        load_session(&env, &owner).started_at > 0 || metered_started_at(&env, &owner).is_some()
+    }
+
+    /// `is_active` usando o timestamp do ledger.
+    pub fn is_active_now(env: Env, owner: Address) -> bool {
+        let now = env.ledger().timestamp();
+        auto_settle(&env, &owner, now);
+        Self::is_active(env, owner, now)
+    }
+
+    /// `remaining` usando o timestamp do ledger.
+    pub fn remaining_now(env: Env, owner: Address) -> u64 {
+        let now = env.ledger().timestamp();
+        observe_expiry(&env, &owner, &load_session(&env, &owner), now);
+        auto_settle(&env, &owner, now);
+        Self::remaining(env, owner, now)
+    }
+
+    /// Consolida a sessão no ledger atual (qualquer um pode chamar): grava o
+    /// saldo restante e, se acabou, encerra a execução.
+    pub fn settle(env: Env, owner: Address) {
+        let now = env.ledger().timestamp();
+        let mut s = load_session(&env, &owner);
+        observe_expiry(&env, &owner, &s, now);
+        if s.started_at > 0 {
+            settle_at(&env, &mut s, now);
+            save_session(&env, &owner, &s);
+        }
+    }
+}
+
+// -------------------- helpers de teste (fora do build de produção) ----------
+#[cfg(any(test, feature = "testutils"))]
+#[contractimpl]
+impl AccessTime {
+    /// Grava a sessão do owner direto no estado (admin), para montar cenários
+    /// de tempo sem repetir buy/grant/start.
+    pub fn set_session_raw(env: Env, owner: Address, remaining_secs: u64, started_at: u64) {
+        require_admin(&env);
+        let mut s = load_session(&env, &owner);
+        s.remaining_secs = remaining_secs;
+        s.started_at = started_at;
+        save_session(&env, &owner, &s);
+    }
+
+    /// Confere as invariantes da sessão do owner (testes de propriedade/fuzzing):
+    /// sessão rodando não começa no futuro nem fica gravada com saldo zero, e o
+    /// saldo ao vivo nunca passa do gravado (mais a folga global). Pausada é
+    /// `started_at == 0` por definição. Devolve `false` na primeira violação.
+    pub fn check_invariants(env: Env, owner: Address) -> bool {
+        let now = env.ledger().timestamp();
+        let s = load_session(&env, &owner);
+        if s.started_at == 0 {
+            return true;
+        }
+        s.started_at <= now
+            && s.remaining_secs > 0
+            && remaining_at(&env, &s, now) <= s.remaining_secs.saturating_add(grace_for(&env, &s))
+    }
 }
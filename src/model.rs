@@ -1,4 +1,4 @@
-use soroban_sdk::{contracterror, contracttype, Address};
+use soroban_sdk::{contracterror, contracttype, Address, BytesN, Symbol, Vec};
 
 // -------------------------------------------------------------
 // MODELO DE DADOS
@@ -12,6 +12,16 @@ pub struct Package {
     pub duration_secs: u32, // duração total concedida ao comprar este pacote
 }
 
+/// Regras de venda de um pacote. Sem registro, o pacote está sempre à venda.
+#[derive(Clone)]
+#[contracttype]
+pub struct SaleRules {
+    pub enabled: bool,      // false = fora do catálogo (não vende)
+    pub starts_at: u64,     // unix ts de início da venda (0 = desde já)
+    pub ends_at: u64,       // unix ts de fim da venda, exclusivo (0 = sem fim)
+    pub stock: Option<u32>, // unidades restantes (None = ilimitado)
+}
+
 /// Estado de sessão com "saldo de segundos" e marcador de início:
 /// - Quando started_at == 0 -> pausado (saldo congelado)
 /// - Quando started_at  > 0 -> consumindo desde 'started_at'
@@ -20,6 +30,49 @@ pub struct Package {
 pub struct Session {
     pub remaining_secs: u64, // saldo de segundos "congeláveis"
     pub started_at: u64,     // unix ts (ledger). 0 = pausado
+    pub not_before: u64,     // unix ts a partir do qual pode iniciar. 0 = imediato
+    pub last_activity: u64,  // unix ts da última gravação (start/pause/crédito)
+}
+
+/// Versão atual do layout de `Session` (v1 = `LegacySession`, sem `not_before`;
+/// v2 = `SessionV2`, sem `last_activity`).
+pub const SESSION_SCHEMA_VERSION: u32 = 3;
+
+/// Layout v1 de `Session`, mantido só para `migrate_session` ler entradas antigas.
+#[derive(Clone)]
+#[contracttype]
+pub struct LegacySession {
+    pub remaining_secs: u64,
+    pub started_at: u64,
+}
+
+/// Layout v2 de `Session`, mantido só para `migrate_session`.
+#[derive(Clone)]
+#[contracttype]
+pub struct SessionV2 {
+    pub remaining_secs: u64,
+    pub started_at: u64,
+    pub not_before: u64,
+}
+
+/// Passe de expiração absoluta: corre no relógio do ledger, sem pausa.
+/// Guarda duração e preço para o reembolso proporcional do tempo não usado.
+#[derive(Clone)]
+#[contracttype]
+pub struct Pass {
+    pub package_id: u32,
+    pub expires_at: u64, // unix ts de expiração
+    pub total_secs: u64, // duração comprada
+    pub price_paid: i128,
+}
+
+/// Saldo de sessão convertido em um registro transferível (tipo NFT): quem
+/// detém o passe pode resgatar os segundos na própria sessão.
+#[derive(Clone)]
+#[contracttype]
+pub struct TimePass {
+    pub secs: u64,
+    pub holder: Address,
 }
 
 /// Estrutura compatível com o modelo "expira em" caso você queira expor
@@ -36,30 +89,222 @@ pub struct Access {
 #[derive(Clone)]
 #[contracttype]
 pub struct OrderRec {
-    pub package_id: u32, // pacote comprado
-    pub credited: bool,  // se os segundos já foram creditados na sessão
+    pub package_id: u32,    // pacote comprado
+    pub credited: bool,     // se os segundos já foram creditados na sessão
+    pub price_paid: i128,   // valor efetivamente cobrado na compra
+    pub escrowed: bool,     // se o pagamento ficou retido no contrato (modo escrow)
+    pub refunded: bool,     // se a ordem foi cancelada e reembolsada
+    pub created_at: u64,    // timestamp do ledger na compra
+    pub credited_secs: u64, // segundos já creditados (grants parciais)
+    pub fee: i128,          // taxa da plataforma sobre price_paid
+    pub fee_at_grant: bool, // taxa ainda retida, a cobrar ao fechar a ordem (FeeTiming::OnGrant)
+    pub token: Address,     // token cobrado na compra; liberação e reembolso saem nele
+}
+
+/// Layout v1 de `OrderRec`, mantido só para `load_order` ler ordens antigas.
+#[derive(Clone)]
+#[contracttype]
+pub struct LegacyOrderRec {
+    pub package_id: u32,
+    pub credited: bool,
+}
+
+/// Momento em que a taxa da plataforma é separada do pagamento.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum FeeTiming {
+    OnBuy = 0,   // na compra, direto do owner para o coletor
+    OnGrant = 1, // no grant: a compra vai inteira para escrow e a taxa sai na liberação
+}
+
+/// Origem dos segundos creditados, para separar tempo pago de cortesia nas métricas.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum GrantSource {
+    Paid = 0, // grant de ordem paga
+    Comp = 1, // admin_credit e códigos promocionais (tempo de cortesia)
+}
+
+/// Estado de uma ordem, derivado das flags de `OrderRec`
+/// (ordem parcialmente creditada ainda conta como pendente).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum OrderStatus {
+    Pending = 0,  // paga, aguardando (o restante do) crédito
+    Credited = 1, // pacote inteiro creditado
+    Refunded = 2, // cancelada e reembolsada
+}
+
+/// Código promocional (tempo grátis) identificado pelo sha256 do código em claro.
+#[derive(Clone)]
+#[contracttype]
+pub struct PromoCode {
+    pub secs: u64,      // segundos creditados a cada resgate
+    pub uses_left: u32, // resgates restantes
+}
+
+/// Fotografia de toda a configuração de instância (retorno de `config`),
+/// com os padrões aplicados aos campos opcionais não configurados.
+#[derive(Clone)]
+#[contracttype]
+pub struct ContractConfig {
+    pub admin: Address,
+    pub token: Address,
+    pub treasury: Address,
+    pub paused: bool,
+    pub escrow_mode: bool,
+    pub max_package_duration_secs: u32,
+    pub max_session_secs: u64, // 0 = sem teto
+    pub cap_clamp: bool,
+    pub min_start_secs: u64,
+    pub refund_window_secs: u64, // 0 = sweep desligado
+    pub stake_threshold: i128,
+    pub stake_discount_bps: u32,
+    pub per_second_price: i128,
+    pub metered_min_secs: u64,
+    pub default_package: Option<u32>,
+    pub splits: Vec<(Address, u32)>,
+    pub reserve_bps: u32,
+    pub admin_grant_allowed: bool,
+    pub max_open_orders: u32, // 0 = sem teto
+    pub fee_collector: Address,
+    pub fee_bps: u32,
+    pub fee_timing: FeeTiming,
+    pub rounding_to_treasury: bool,
+    pub self_grant_allowed: bool,
+    pub credit_per_sec: i128,
+    pub max_idle_secs: u64, // 0 = sem expiração por inatividade
+    pub min_active_secs: u64,
+    pub auto_settle: bool,
+    pub catalog_frozen: bool,
+    pub global_order_mode: bool,
+    pub grant_delay_secs: u64,
+    pub global_grace_secs: u64,
+    pub daily_cap_secs: u64, // 0 = sem teto
+    pub consumption_round_secs: u64,
+    pub strict_grant: bool,
 }
 
 /// Chaves de armazenamento:
-/// - Instance storage: Admin / Token / Package / NextOrder
+/// - Instance storage: Admin / Token / Treasury / Package / NextOrder / flags de config
 ///   (config/global + contador determinístico por dono)
 /// - Persistent storage:
 ///     - Session(owner)            -> estado por usuário (vida longa)
 ///     - Order(owner, order_id)    -> ordem paga, pendente ou já creditada
+///     - PromoCode(hash)           -> código promocional e usos restantes
+///     - PausedAt / TotalPausedSecs(owner) -> auditoria do tempo pausado
+///     - Staked(owner)             -> saldo em stake
 #[contracttype]
 pub enum DataKey {
     Admin,        // Address do administrador do catálogo
     Token,        // Address do contrato do token (SAC) usado na cobrança
     Package(u32), // id -> Package
     // NOVO: contador sequencial por dono para gerar order_id determinístico
-    NextOrder(Address),   // owner -> u128 (próximo order_id disponível)
-    Session(Address),     // owner -> Session
-    Order(Address, u128), // (owner, order_id) -> OrderRec
+    NextOrder(Address),            // owner -> u128 (próximo order_id disponível)
+    Session(Address),              // owner -> Session
+    Order(Address, u128),          // (owner, order_id) -> OrderRec
+    MaxPackageDurationSecs,        // u32: teto de duration_secs aceito em set_package
+    PackageGrantedSecs(u32),       // id -> (vezes creditado, total de segundos creditados)
+    PromoCode(BytesN<32>),         // sha256(código) -> PromoCode
+    Treasury,                      // Address que recebe os pagamentos (padrão: admin)
+    EscrowMode,                    // bool: pagamento fica no contrato até o grant
+    Splits,                        // Vec<(Address, u32)>: divisão do pagamento em bps (soma 10000)
+    PausedAt(Address),             // owner -> u64: ts da última pausa (intervalo ainda aberto)
+    TotalPausedSecs(Address),      // owner -> u64: soma dos intervalos pausa -> próximo start
+    Staked(Address),               // owner -> i128: tokens em stake custodiados pelo contrato
+    StakeThreshold,                // i128: stake mínimo (exclusivo) para ganhar desconto
+    StakeDiscountBps,              // u32: desconto em bps aplicado no buy_order para stakers
+    MinStartSecs,                  // u64: saldo mínimo para iniciar uma sessão (0 = sem mínimo)
+    MaxSessionSecs,                // u64: teto do saldo de uma sessão (0 = sem teto)
+    CapClamp,                      // bool: true = grant acima do teto é truncado; false = rejeitado
+    PerSecondPrice,                // i128: preço por segundo no modo medido (metered)
+    MeteredMinSecs, // u64: segundos mínimos que o allowance precisa cobrir no start_metered
+    MeteredStart(Address), // owner -> u64: início da sessão medida em aberto
+    GrantRef(Address, BytesN<32>), // (owner, referência externa) -> u64 (saldo devolvido no grant original)
+    DefaultPackage,                // u32: pacote comprado por buy_default (compra em um clique)
+    RefundWindowSecs, // u64: idade a partir da qual ordem pendente é reembolsável pelo sweep (0 = off)
+    Paused,           // bool: contrato pausado (bloqueia compra, grant e start)
+    SessionSchema(Address), // owner -> u32: versão do layout de Session gravado
+    GrantedSecs(GrantSource), // u64: total de segundos creditados por origem (pago / cortesia)
+    ReserveBps,       // u32: fração (bps) de cada pagamento retida no contrato como reserva
+    ReserveBalance,   // i128: reserva retida ainda não sacada
+    PackageSale(u32), // id -> SaleRules (habilitado, janela de venda, estoque)
+    Pass(Address),    // owner -> Pass: passe de expiração absoluta (não pausável)
+    EventSeq,         // u64: contador global de eventos (último tópico de todo evento publicado)
+    AdminGrantAllowed, // bool: admin pode creditar ordens de terceiros (padrão: true)
+    ActiveCount,      // u32: sessões rodando (aproximado: expiração só conta ao consolidar)
+    MaxOpenOrders,    // u32: teto de ordens pendentes por owner (0 = sem teto)
+    OpenOrders(Address), // owner -> u32: ordens pendentes (nem creditadas nem reembolsadas)
+    SignerKey(Address), // owner -> BytesN<32>: chave ed25519 que assina grants off-chain
+    GrantNonce(Address), // owner -> u64: próximo nonce aceito em grant_signed
+    Delegate(Address), // owner -> Address: delegado que pode dar start/pause na sessão
+    CreditedCount(Address), // owner -> u32: ordens totalmente creditadas
+    Credit(Address),  // owner -> i128: crédito pré-pago custodiado pelo contrato
+    CreditPerSec,     // i128: crédito debitado por segundo em buy_time_from_credit
+    FeeCollector,     // Address que recebe a taxa da plataforma (padrão: tesouraria)
+    FeeBps,           // u32: taxa da plataforma em bps sobre o preço da ordem
+    FeeTiming,        // FeeTiming: taxa cobrada na compra (padrão) ou no grant
+    GroupSession(Symbol), // grupo -> Session compartilhada entre os membros
+    GroupOwner(Symbol), // grupo -> Address do dono (gerencia membros)
+    GroupMember(Symbol, Address), // (grupo, membro) -> bool
+}
+
+/// Continuação de `DataKey`: o `contracttype` limita um enum a 50 variantes.
+/// Chaves novas entram aqui (mesmo esquema de instance/persistent de `DataKey`).
+#[contracttype]
+pub enum DataKeyExt {
+    RoundingToTreasury, // bool: sobras de divisão ficam do lado da tesouraria (padrão) ou vão ao coletor da taxa
+    TimePass(u128),     // pass_id -> TimePass: saldo de sessão tokenizado e transferível
+    NextTimePass,       // u128: último pass_id emitido
+    ExpiryNotified(Address), // owner -> u64: started_at da execução cujo fim já foi notificado
+    PackageCooldownSecs(u32), // id -> u64: intervalo mínimo entre grants do pacote por owner (0 = off)
+    LastGrant(Address, u32),  // (owner, id) -> u64: ts do último grant do pacote
+    SelfGrantAllowed,         // bool: owner pode creditar as próprias ordens (padrão: true)
+    OpenOrderValue, // i128: soma do valor reembolsável das ordens pendentes (todos os owners)
+    NamedSession(Address, Symbol), // (owner, nome) -> Session: slot de sessão por dispositivo
+    SessionNames(Address), // owner -> Vec<Symbol>: nomes dos slots já criados
+    MaxIdleSecs,    // u64: tempo máximo pausada antes de o saldo poder ser confiscado (0 = off)
+    MinActiveSecs,  // u64: tempo mínimo rodando antes de aceitar pause (0 = off)
+    AutoSettle,     // bool: leituras que veem sessão expirada já gravam o encerramento
+    PriceTiers(u32), // id -> Vec<(min_qty, discount_bps)>: faixas de desconto por quantidade, min_qty crescente
+    Blocked(Address), // owner -> bool: bloqueado pelo admin (sem compras, grants nem starts)
+    CatalogFrozen,   // bool: catálogo congelado (irreversível)
+    GlobalOrderMode, // bool: ids de ordem num contador único para todos os owners
+    NextGlobalOrder, // u128: último order_id global emitido
+    GlobalOrder(u128), // order_id -> (owner, OrderRec) no modo global
+    ConsumedSecs(Address), // owner -> u64: total de segundos efetivamente consumidos (consolidados)
+    GrantDelaySecs,  // u64: espera mínima entre a compra e o grant da ordem (0 = off)
+    GlobalGraceOffset, // (u64, u64): folga p/ sessões que rodavam ao ser definida e o ts da definição
+    DailyCapSecs,      // u64: teto de segundos consumidos por owner por dia UTC (0 = off)
+    DailyUsage(Address), // (dia UTC, segundos consumidos nesse dia) do owner
+    ConsumptionRoundSecs, // u64: arredonda o consumo consolidado para cima a esse múltiplo (0/1 = off)
+    PackageIds,           // Vec<u32>: ids do catálogo na ordem de cadastro
+    PackageUpdatedAt(u32), // u64: ts da última escrita do pacote (set_package)
+    StrictGrant,          // bool: grant reverte se o pacote mudou depois da compra
+    RunStartedAt(BytesN<32>), // session_tag -> u64: início da execução em andamento (MinActiveSecs, arredondamento)
+    CapHeld(BytesN<32>), // sha256 do XDR da chave da sessão -> u64: saldo retido pelo teto diário na execução
+    TotalStaked,         // i128: soma dos stakes custodiados (trava o set_token)
+    TotalCredit,         // i128: soma dos créditos pré-pagos custodiados (trava o set_token)
+    OpenMetered,         // u32: sessões medidas em aberto (trava o set_token)
+    PassesLiveUntil,     // u64: maior expires_at entre os passes vendidos (trava o set_token)
+    ReserveOf(Address), // i128: reserva retida num token anterior ao atual (ReserveBalance = token atual)
+    NamedExpiryNotified(Address, Symbol), // u64: started_at da execução do slot cujo fim já foi notificado
 }
 
+/// Versão do layout dos eventos (tópicos e corpo). Incremente ao mudar qualquer
+/// evento para que indexadores detectem a incompatibilidade.
+/// v1: EventSeq como último tópico de todo evento.
+/// v2: `on_sale` no fim do corpo de ("purchase", "created").
+pub const EVENT_SCHEMA_VERSION: u32 = 2;
+
 // -------------------------------------------------------------
 // ERROS
 // -------------------------------------------------------------
+
+/// Quantidade de variantes de `Error` (códigos 1..=ERROR_CODE_COUNT).
+/// Ao adicionar um erro, atualize este valor e o teste de introspecção.
+pub const ERROR_CODE_COUNT: u32 = 36;
+
 #[contracterror]
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum Error {
@@ -72,4 +317,34 @@ pub enum Error {
     // novos para o fluxo buy_order + grant
     OrderNotFound = 6,  // ordem não existe (ex.: order_id inválido)
     AlreadyGranted = 7, // ordem já foi creditada (idempotência no grant)
+
+    InvalidPackage = 8, // pacote com parâmetros fora dos limites (ex.: duração acima do teto)
+    CodeNotFound = 9,   // código promocional inexistente
+    CodeExhausted = 10, // código promocional sem usos restantes
+    InvalidOrderState = 11, // ordem em estado incompatível com a operação
+    InvalidFee = 12,    // configuração de taxa/divisão inválida (ex.: pesos não somam 10000)
+    InvalidAmount = 13, // valor não positivo onde se exige um montante
+    BelowMinStart = 14, // saldo abaixo do mínimo exigido para iniciar
+    SessionCapExceeded = 15, // crédito levaria o saldo acima de MaxSessionSecs
+    NotYetAvailable = 16, // sessão agendada: ainda antes de not_before
+    NoDefaultPackage = 17, // buy_default sem pacote padrão configurado
+    ContractPaused = 18, // operação bloqueada com o contrato pausado
+    SchemaMismatch = 19, // Session gravada em layout desconhecido
+    PackageUnavailable = 20, // pacote desabilitado, fora da janela de venda ou esgotado
+    BatchTooLarge = 21, // lote acima de MAX_BATCH entradas
+    TooManyOpenOrders = 22, // owner já tem MaxOpenOrders ordens pendentes
+    NonceReused = 23,   // nonce de grant_signed já usado (replay)
+    GroupNotFound = 24, // grupo inexistente
+    PriceExceedsMax = 25, // preço cobrado acima do max_price aceito pelo comprador
+    PassNotFound = 26,  // TimePass inexistente (ou já resgatado)
+    GrantCooldown = 27, // mesmo pacote creditado ao owner há menos que o cooldown
+    SessionIdleExpired = 28, // sessão pausada além de MaxIdleSecs
+    TooSoonToPause = 29, // pause antes de MinActiveSecs desde o start
+    OwnerBlocked = 30,  // owner bloqueado pelo admin
+    CatalogFrozen = 31, // alteração de pacote com o catálogo congelado
+    GrantTooEarly = 32, // grant antes de GrantDelaySecs desde a compra
+    DailyCapReached = 33, // start com o teto diário de consumo já atingido
+    PackageChanged = 34, // StrictGrant: pacote editado depois da compra da ordem
+    InvalidTokenDecimals = 35, // decimals do token grandes demais para o fator de escala em i128
+    TokenInUse = 36,    // set_token com saldos ainda pagáveis no token atual
 }
@@ -1,13 +1,79 @@
 #![cfg(test)]
+extern crate std;
 
 use soroban_sdk::{
-    testutils::{Address as _, Ledger},
+    contract, contractimpl, symbol_short,
+    testutils::{Address as _, Events, Ledger, MockAuth, MockAuthInvoke},
     token::{Client as TokenClient, StellarAssetClient},
-    Address, Env,
+    vec,
+    xdr::{self, ToXdr},
+    Address, Bytes, BytesN, Env, IntoVal, String, Symbol, TryFromVal, Val, Vec,
 };
 
+use ed25519_dalek::{Signer, SigningKey};
+use std::rc::Rc;
+
 use crate::contract::{AccessTime, AccessTimeClient};
-use crate::model::Session;
+use crate::model::{
+    DataKey, Error, FeeTiming, GrantSource, LegacyOrderRec, LegacySession, OrderStatus, SaleRules,
+    Session, SessionV2, ERROR_CODE_COUNT, EVENT_SCHEMA_VERSION,
+};
+
+/// Tópicos do evento sem o EventSeq (sempre o último tópico).
+fn without_seq(topics: &Vec<Val>) -> Vec<Val> {
+    topics.slice(0..topics.len() - 1)
+}
+
+// Registra SAC + contrato, faz o init e minta saldo para `user`.
+fn setup<'a>(env: &'a Env, user: &Address) -> (AccessTimeClient<'a>, Address, Address) {
+    env.mock_all_auths();
+    env.ledger().with_mut(|li| li.timestamp = 1000000);
+
+    let admin = Address::generate(env);
+    let token_id = env
+        .register_stellar_asset_contract_v2(admin.clone())
+        .address();
+    let contract_id = env.register(AccessTime, (None::<Address>, None::<Address>));
+    let client = AccessTimeClient::new(env, &contract_id);
+    client.init(&admin, &token_id);
+
+    StellarAssetClient::new(env, &token_id).mint(user, &1000_i128);
+    (client, admin, token_id)
+}
+
+// Cria uma conta clássica (chave `[seed; 32]`) com `balance` stroops de XLM
+// nativo e devolve o endereço.
+fn fund_native_account(env: &Env, seed: u8, balance: i64) -> Address {
+    let pk = [seed; 32];
+    let account_id = xdr::AccountId(xdr::PublicKey::PublicKeyTypeEd25519(xdr::Uint256(pk)));
+    let key = Rc::new(xdr::LedgerKey::Account(xdr::LedgerKeyAccount {
+        account_id: account_id.clone(),
+    }));
+    let entry = Rc::new(xdr::LedgerEntry {
+        data: xdr::LedgerEntryData::Account(xdr::AccountEntry {
+            account_id: account_id.clone(),
+            balance,
+            flags: 0,
+            home_domain: Default::default(),
+            inflation_dest: None,
+            num_sub_entries: 0,
+            seq_num: xdr::SequenceNumber(0),
+            thresholds: xdr::Thresholds([1; 4]),
+            signers: xdr::VecM::default(),
+            ext: xdr::AccountEntryExt::V0,
+        }),
+        last_modified_ledger_seq: 0,
+        ext: xdr::LedgerEntryExt::V0,
+    });
+    let host = env.host();
+    host.with_mut_storage(|storage| storage.put(&key, &entry, None, &host.budget_cloned()))
+        .unwrap();
+    Address::try_from_val(
+        env,
+        &xdr::ScVal::Address(xdr::ScAddress::Account(account_id)),
+    )
+    .unwrap()
+}
 
 #[test]
 fn test_buy_order_and_grant_flow() {
@@ -24,7 +90,7 @@ fn test_buy_order_and_grant_flow() {
     let token_id = env
         .register_stellar_asset_contract_v2(admin.clone())
         .address();
-    let contract_id = env.register(AccessTime, ());
+    let contract_id = env.register(AccessTime, (None::<Address>, None::<Address>));
     let client = AccessTimeClient::new(&env, &contract_id);
 
     // Initialize contract and create package
@@ -71,7 +137,7 @@ fn test_buy_order_insufficient_balance() {
     let token_id = env
         .register_stellar_asset_contract_v2(admin.clone())
         .address();
-    let contract_id = env.register(AccessTime, ());
+    let contract_id = env.register(AccessTime, (None::<Address>, None::<Address>));
     let client = AccessTimeClient::new(&env, &contract_id);
 
     client.init(&admin, &token_id);
@@ -101,7 +167,7 @@ fn test_buy_order_package_not_found() {
     let token_id = env
         .register_stellar_asset_contract_v2(admin.clone())
         .address();
-    let contract_id = env.register(AccessTime, ());
+    let contract_id = env.register(AccessTime, (None::<Address>, None::<Address>));
     let client = AccessTimeClient::new(&env, &contract_id);
 
     client.init(&admin, &token_id);
@@ -130,7 +196,7 @@ fn test_grant_twice_should_fail() {
     let token_id = env
         .register_stellar_asset_contract_v2(admin.clone())
         .address();
-    let contract_id = env.register(AccessTime, ());
+    let contract_id = env.register(AccessTime, (None::<Address>, None::<Address>));
     let client = AccessTimeClient::new(&env, &contract_id);
 
     client.init(&admin, &token_id);
@@ -159,7 +225,7 @@ fn test_admin_can_grant_user_order() {
     let token_id = env
         .register_stellar_asset_contract_v2(admin.clone())
         .address();
-    let contract_id = env.register(AccessTime, ());
+    let contract_id = env.register(AccessTime, (None::<Address>, None::<Address>));
     let client = AccessTimeClient::new(&env, &contract_id);
 
     client.init(&admin, &token_id);
@@ -194,7 +260,7 @@ fn test_debug_buy_order_package_2() {
     let token_id = env
         .register_stellar_asset_contract_v2(admin.clone())
         .address();
-    let contract_id = env.register(AccessTime, ());
+    let contract_id = env.register(AccessTime, (None::<Address>, None::<Address>));
     let client = AccessTimeClient::new(&env, &contract_id);
 
     client.init(&admin, &token_id);
@@ -238,7 +304,7 @@ fn buy_start_pause_flow() {
     let token_id = sac.address();
 
     // 2) Registra nosso contrato e cria o client auto-gerado
-    let contract_id = env.register(AccessTime, ());
+    let contract_id = env.register(AccessTime, (None::<Address>, None::<Address>));
     let client = AccessTimeClient::new(&env, &contract_id);
 
     // 3) init + pacote "10 XML = 1h"
@@ -318,7 +384,7 @@ fn test_debug_buy_order_step_by_step() {
     let token_id = env
         .register_stellar_asset_contract_v2(admin.clone())
         .address();
-    let contract_id = env.register(AccessTime, ());
+    let contract_id = env.register(AccessTime, (None::<Address>, None::<Address>));
     let client = AccessTimeClient::new(&env, &contract_id);
 
     // Debug: Check if init works
@@ -358,7 +424,7 @@ fn test_minimal_buy_order() {
     let token_id = env
         .register_stellar_asset_contract_v2(admin.clone())
         .address();
-    let contract_id = env.register(AccessTime, ());
+    let contract_id = env.register(AccessTime, (None::<Address>, None::<Address>));
     let client = AccessTimeClient::new(&env, &contract_id);
 
     client.init(&admin, &token_id);
@@ -383,7 +449,7 @@ fn test_order_id_monotonic_per_owner() {
     let token_id = env
         .register_stellar_asset_contract_v2(admin.clone())
         .address();
-    let contract_id = env.register(AccessTime, ());
+    let contract_id = env.register(AccessTime, (None::<Address>, None::<Address>));
     let client = AccessTimeClient::new(&env, &contract_id);
 
     client.init(&admin, &token_id);
@@ -413,7 +479,7 @@ fn test_order_id_isolation_between_owners() {
     let token_id = env
         .register_stellar_asset_contract_v2(admin.clone())
         .address();
-    let contract_id = env.register(AccessTime, ());
+    let contract_id = env.register(AccessTime, (None::<Address>, None::<Address>));
     let client = AccessTimeClient::new(&env, &contract_id);
 
     client.init(&admin, &token_id);
@@ -431,3 +497,3492 @@ fn test_order_id_isolation_between_owners() {
     assert_eq!(b1, 1);
     assert_eq!(a2, 2);
 }
+
+#[test]
+#[should_panic(expected = "Error(Contract, #8)")]
+fn test_set_package_rejects_absurd_duration() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+
+    // ~136 anos: muito acima do teto padrão de 365 dias
+    client.set_package(&1u32, &10_i128, &u32::MAX);
+}
+
+#[test]
+fn test_set_package_accepts_duration_within_cap() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+
+    client.set_max_package_duration(&86_400u32);
+    client.set_package(&1u32, &10_i128, &86_400u32);
+    assert_eq!(client.get_package(&1u32).duration_secs, 86_400);
+
+    let res = client.try_set_package(&2u32, &10_i128, &86_401u32);
+    assert!(res.is_err());
+}
+
+#[test]
+fn test_token_metadata_reads_sac_decimals() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, token_id) = setup(&env, &user);
+
+    let token = TokenClient::new(&env, &token_id);
+    let (scale, d, name) = client.token_metadata();
+    assert_eq!(d, token.decimals());
+    assert_eq!(d, 7); // SAC sempre usa 7 casas
+    assert_eq!(scale, 10_000_000);
+    assert_eq!(name, token.name());
+}
+
+// Token de teste com decimals absurdos (o fator 10^40 não cabe em i128).
+#[contract]
+struct HugeDecimalsToken;
+
+#[contractimpl]
+impl HugeDecimalsToken {
+    pub fn decimals(_env: Env) -> u32 {
+        40
+    }
+
+    pub fn name(env: Env) -> String {
+        String::from_str(&env, "huge")
+    }
+}
+
+#[test]
+fn test_token_metadata_rejects_overflowing_decimals() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    let huge = env.register(HugeDecimalsToken, ());
+    client.set_token(&huge);
+    assert_eq!(
+        client.try_token_metadata(),
+        Err(Ok(Error::InvalidTokenDecimals.into()))
+    );
+}
+
+#[test]
+fn test_package_stats_track_each_plan() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+
+    client.set_package(&1u32, &10_i128, &3600u32);
+    client.set_package(&2u32, &20_i128, &7200u32);
+
+    for _ in 0..2 {
+        let id = client.buy_order(&user, &1u32);
+        client.grant(&user, &user, &id);
+    }
+    let id = client.buy_order(&user, &2u32);
+    client.grant(&user, &user, &id);
+
+    // ordem comprada mas não creditada não conta
+    client.buy_order(&user, &2u32);
+
+    assert_eq!(client.package_stats(&1u32), (2, 7200));
+    assert_eq!(client.package_stats(&2u32), (1, 7200));
+    assert_eq!(client.package_stats(&3u32), (0, 0));
+}
+
+#[test]
+fn test_grant_on_running_session_keeps_banked_time() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &10_i128, &3600u32);
+
+    let id1 = client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &id1);
+    client.start(&user);
+
+    // consome 600s e credita mais um pacote com a sessão rodando
+    let t0 = env.ledger().timestamp();
+    env.ledger().with_mut(|li| li.timestamp = t0 + 600);
+    let id2 = client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &id2);
+
+    let now = env.ledger().timestamp();
+    let s = client.get_session(&user);
+    assert_eq!(s.started_at, now); // rebaseado, continua ativa
+    assert_eq!(client.remaining(&user, &now), (3600 - 600) + 3600);
+}
+
+#[test]
+fn test_grant_after_expiry_does_not_consume_new_time() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &10_i128, &3600u32);
+
+    let id1 = client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &id1);
+    client.start(&user);
+
+    // sessão expira (e passa bem do fim) sem ninguém pausar
+    let t0 = env.ledger().timestamp();
+    env.ledger().with_mut(|li| li.timestamp = t0 + 5000);
+    let id2 = client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &id2);
+
+    let now = env.ledger().timestamp();
+    let s = client.get_session(&user);
+    assert_eq!(s.started_at, 0);
+    assert_eq!(s.remaining_secs, 3600);
+    assert_eq!(client.remaining(&user, &now), 3600);
+}
+
+#[test]
+fn test_set_packages_seeds_catalog_in_one_call() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+
+    let mut catalog = vec![&env];
+    for id in 1u32..=5 {
+        catalog.push_back((id, 10_i128 * id as i128, 1800u32 * id));
+    }
+    client.set_packages(&catalog);
+
+    for id in 1u32..=5 {
+        let pkg = client.get_package(&id);
+        assert_eq!(pkg.price, 10 * id as i128);
+        assert_eq!(pkg.duration_secs, 1800 * id);
+    }
+}
+
+#[test]
+fn test_set_packages_rejects_whole_batch_on_invalid_entry() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+
+    let catalog = vec![&env, (1u32, 10_i128, 3600u32), (2u32, 20_i128, u32::MAX)];
+    assert!(client.try_set_packages(&catalog).is_err());
+    assert!(client.try_get_package(&1u32).is_err());
+}
+
+#[test]
+fn test_promo_code_limited_uses() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+
+    let code = Bytes::from_slice(&env, b"BLACKFRIDAY");
+    let code_hash = env.crypto().sha256(&code).to_bytes();
+    client.create_code(&code_hash, &900u64, &2u32);
+
+    client.redeem_code(&user, &code);
+    client.redeem_code(&user, &code);
+    assert_eq!(client.get_session(&user).remaining_secs, 1800);
+
+    // terceiro resgate: esgotado
+    let res = client.try_redeem_code(&user, &code);
+    assert_eq!(res, Err(Ok(Error::CodeExhausted.into())));
+    assert_eq!(client.get_session(&user).remaining_secs, 1800);
+    assert_eq!(client.granted_secs(&GrantSource::Comp), 1800);
+}
+
+#[test]
+fn test_promo_code_blocked_while_paused() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    let code = Bytes::from_slice(&env, b"OUTAGE");
+    client.create_code(&env.crypto().sha256(&code).to_bytes(), &900u64, &1u32);
+
+    client.set_paused(&true);
+    assert_eq!(
+        client.try_redeem_code(&user, &code),
+        Err(Ok(Error::ContractPaused.into()))
+    );
+    client.set_paused(&false);
+    client.redeem_code(&user, &code);
+    assert_eq!(client.get_session(&user).remaining_secs, 900);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #9)")]
+fn test_promo_code_unknown() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+
+    client.redeem_code(&user, &Bytes::from_slice(&env, b"NOPE"));
+}
+
+#[test]
+fn test_reemit_grant_publishes_current_state() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &10_i128, &3600u32);
+
+    let order_id = client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &order_id);
+    client.reemit_grant(&user, &order_id);
+
+    let (contract, topics, data) = env.events().all().last().unwrap();
+    assert_eq!(contract, client.address);
+    let expected: Vec<Val> = (symbol_short!("grant"), user.clone(), order_id).into_val(&env);
+    assert_eq!(without_seq(&topics), expected);
+    let remaining: u64 = data.into_val(&env);
+    assert_eq!(remaining, 3600);
+}
+
+#[test]
+fn test_reemit_grant_rejects_uncredited_order() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &10_i128, &3600u32);
+
+    let order_id = client.buy_order(&user, &1u32);
+    let res = client.try_reemit_grant(&user, &order_id);
+    assert_eq!(res, Err(Ok(Error::InvalidOrderState.into())));
+}
+
+#[test]
+fn test_escrow_buy_holds_funds_and_grant_releases() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, token_id) = setup(&env, &user);
+    let treasury = Address::generate(&env);
+    let token = TokenClient::new(&env, &token_id);
+
+    client.set_treasury(&treasury);
+    client.set_escrow_mode(&true);
+    client.set_package(&1u32, &100_i128, &3600u32);
+
+    let order_id = client.buy_order(&user, &1u32);
+    assert_eq!(token.balance(&user), 900);
+    assert_eq!(token.balance(&client.address), 100);
+    assert_eq!(token.balance(&treasury), 0);
+
+    client.grant(&user, &user, &order_id);
+    assert_eq!(token.balance(&client.address), 0);
+    assert_eq!(token.balance(&treasury), 100);
+    assert_eq!(client.get_session(&user).remaining_secs, 3600);
+}
+
+#[test]
+fn test_escrow_cancel_refunds_from_contract() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, token_id) = setup(&env, &user);
+    let token = TokenClient::new(&env, &token_id);
+
+    client.set_escrow_mode(&true);
+    client.set_package(&1u32, &100_i128, &3600u32);
+
+    let order_id = client.buy_order(&user, &1u32);
+    client.cancel_order(&user, &order_id);
+    assert_eq!(token.balance(&user), 1000);
+    assert_eq!(token.balance(&client.address), 0);
+
+    // ordem cancelada não pode mais ser creditada nem cancelada de novo
+    let res = client.try_grant(&user, &user, &order_id);
+    assert_eq!(res, Err(Ok(Error::InvalidOrderState.into())));
+    let res = client.try_cancel_order(&user, &order_id);
+    assert_eq!(res, Err(Ok(Error::InvalidOrderState.into())));
+}
+
+#[test]
+fn test_cancel_rejects_order_paid_to_treasury() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, admin, token_id) = setup(&env, &user);
+
+    client.set_package(&1u32, &100_i128, &3600u32);
+    let order_id = client.buy_order(&user, &1u32);
+    assert_eq!(TokenClient::new(&env, &token_id).balance(&admin), 100);
+
+    let res = client.try_cancel_order(&user, &order_id);
+    assert_eq!(res, Err(Ok(Error::InvalidOrderState.into())));
+}
+
+#[test]
+fn test_can_grant_reports_order_state() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &10_i128, &3600u32);
+
+    let order_id = client.buy_order(&user, &1u32);
+    assert!(client.can_grant(&user, &order_id));
+
+    client.grant(&user, &user, &order_id);
+    assert!(!client.can_grant(&user, &order_id));
+
+    // ordem inexistente
+    assert!(!client.can_grant(&user, &99u128));
+}
+
+#[test]
+fn test_splits_distribute_payment_by_weight() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, token_id) = setup(&env, &user);
+    let token = TokenClient::new(&env, &token_id);
+    let (a, b, c) = (
+        Address::generate(&env),
+        Address::generate(&env),
+        Address::generate(&env),
+    );
+
+    client.set_splits(&vec![
+        &env,
+        (a.clone(), 5000u32),
+        (b.clone(), 3000u32),
+        (c.clone(), 2000u32),
+    ]);
+    client.set_package(&1u32, &101_i128, &3600u32);
+    client.buy_order(&user, &1u32);
+
+    // 50.5 / 30.3 / 20.2 -> 50 / 30 / 20 e a sobra (1) vai para o primeiro
+    assert_eq!(token.balance(&a), 51);
+    assert_eq!(token.balance(&b), 30);
+    assert_eq!(token.balance(&c), 20);
+    assert_eq!(token.balance(&user), 1000 - 101);
+}
+
+#[test]
+fn test_splits_must_sum_to_10000() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+
+    let splits = vec![
+        &env,
+        (Address::generate(&env), 5000u32),
+        (Address::generate(&env), 4000u32),
+    ];
+    let res = client.try_set_splits(&splits);
+    assert_eq!(res, Err(Ok(Error::InvalidFee.into())));
+}
+
+#[test]
+fn test_paused_secs_accumulates_idle_gaps() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &10_i128, &3600u32);
+    let order_id = client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &order_id);
+
+    let advance = |secs: u64| {
+        let t = env.ledger().timestamp();
+        env.ledger().with_mut(|li| li.timestamp = t + secs);
+    };
+
+    // antes da primeira pausa não há tempo pausado
+    client.start(&user);
+    advance(100);
+    client.pause(&user);
+    advance(50); // pausado 50
+    client.start(&user);
+    advance(100);
+    client.pause(&user);
+    advance(200); // pausado 200
+    client.start(&user);
+    assert_eq!(client.paused_secs(&user), 250);
+
+    // intervalo em aberto também conta
+    client.pause(&user);
+    advance(30);
+    assert_eq!(client.paused_secs(&user), 280);
+}
+
+#[test]
+fn test_staker_above_threshold_buys_at_discount() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, admin, token_id) = setup(&env, &user);
+    let token = TokenClient::new(&env, &token_id);
+
+    client.set_package(&1u32, &100_i128, &3600u32);
+    client.set_stake_discount(&200_i128, &1000u32); // 10% acima de 200 em stake
+    client.stake(&user, &300_i128);
+    assert_eq!(token.balance(&client.address), 300);
+
+    client.buy_order(&user, &1u32);
+    assert_eq!(token.balance(&admin), 90);
+    assert_eq!(token.balance(&user), 1000 - 300 - 90);
+
+    // unstake devolve os tokens e remove o desconto
+    client.unstake(&user, &300_i128);
+    assert_eq!(client.get_staked(&user), 0);
+    client.buy_order(&user, &1u32);
+    assert_eq!(token.balance(&admin), 190);
+    assert_eq!(token.balance(&user), 1000 - 90 - 100);
+}
+
+#[test]
+fn test_non_staker_pays_full_price() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, admin, token_id) = setup(&env, &user);
+
+    client.set_package(&1u32, &100_i128, &3600u32);
+    client.set_stake_discount(&200_i128, &1000u32);
+    client.stake(&user, &200_i128); // igual ao limiar: não passa dele
+
+    client.buy_order(&user, &1u32);
+    assert_eq!(TokenClient::new(&env, &token_id).balance(&admin), 100);
+
+    let res = client.try_unstake(&user, &201_i128);
+    assert_eq!(res, Err(Ok(Error::InsufficientBalance.into())));
+}
+
+// match exaustivo: uma variante nova sem entrada aqui não compila
+fn error_code(e: Error) -> u32 {
+    match e {
+        Error::AlreadyInitialized => 1,
+        Error::NotInitialized => 2,
+        Error::Unauthorized => 3,
+        Error::PackageNotFound => 4,
+        Error::InsufficientBalance => 5,
+        Error::OrderNotFound => 6,
+        Error::AlreadyGranted => 7,
+        Error::InvalidPackage => 8,
+        Error::CodeNotFound => 9,
+        Error::CodeExhausted => 10,
+        Error::InvalidOrderState => 11,
+        Error::InvalidFee => 12,
+        Error::InvalidAmount => 13,
+        Error::BelowMinStart => 14,
+        Error::SessionCapExceeded => 15,
+        Error::NotYetAvailable => 16,
+        Error::NoDefaultPackage => 17,
+        Error::ContractPaused => 18,
+        Error::SchemaMismatch => 19,
+        Error::PackageUnavailable => 20,
+        Error::BatchTooLarge => 21,
+        Error::TooManyOpenOrders => 22,
+        Error::NonceReused => 23,
+        Error::GroupNotFound => 24,
+        Error::PriceExceedsMax => 25,
+        Error::PassNotFound => 26,
+        Error::GrantCooldown => 27,
+        Error::SessionIdleExpired => 28,
+        Error::TooSoonToPause => 29,
+        Error::OwnerBlocked => 30,
+        Error::CatalogFrozen => 31,
+        Error::GrantTooEarly => 32,
+        Error::DailyCapReached => 33,
+        Error::PackageChanged => 34,
+        Error::InvalidTokenDecimals => 35,
+        Error::TokenInUse => 36,
+    }
+}
+
+#[test]
+fn test_error_code_count_matches_enum() {
+    let env = Env::default();
+    let contract_id = env.register(AccessTime, (None::<Address>, None::<Address>));
+    let client = AccessTimeClient::new(&env, &contract_id);
+
+    let all = [
+        Error::AlreadyInitialized,
+        Error::NotInitialized,
+        Error::Unauthorized,
+        Error::PackageNotFound,
+        Error::InsufficientBalance,
+        Error::OrderNotFound,
+        Error::AlreadyGranted,
+        Error::InvalidPackage,
+        Error::CodeNotFound,
+        Error::CodeExhausted,
+        Error::InvalidOrderState,
+        Error::InvalidFee,
+        Error::InvalidAmount,
+        Error::BelowMinStart,
+        Error::SessionCapExceeded,
+        Error::NotYetAvailable,
+        Error::NoDefaultPackage,
+        Error::ContractPaused,
+        Error::SchemaMismatch,
+        Error::PackageUnavailable,
+        Error::BatchTooLarge,
+        Error::TooManyOpenOrders,
+        Error::NonceReused,
+        Error::GroupNotFound,
+        Error::PriceExceedsMax,
+        Error::PassNotFound,
+        Error::GrantCooldown,
+        Error::SessionIdleExpired,
+        Error::TooSoonToPause,
+        Error::OwnerBlocked,
+        Error::CatalogFrozen,
+        Error::GrantTooEarly,
+        Error::DailyCapReached,
+        Error::PackageChanged,
+        Error::InvalidTokenDecimals,
+        Error::TokenInUse,
+    ];
+    assert_eq!(client.error_code_count(), ERROR_CODE_COUNT);
+    assert_eq!(all.len() as u32, ERROR_CODE_COUNT);
+    for (i, e) in all.iter().enumerate() {
+        // códigos contíguos e sem buracos
+        assert_eq!(*e as u32, i as u32 + 1);
+        assert_eq!(error_code(*e), *e as u32);
+    }
+}
+
+#[test]
+fn test_grant_ex_start_now() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &10_i128, &3600u32);
+
+    let order_id = client.buy_order(&user, &1u32);
+    client.grant_ex(&user, &user, &order_id, &true);
+
+    let now = env.ledger().timestamp();
+    let s = client.get_session(&user);
+    assert_eq!(s.started_at, now);
+    assert!(client.is_active(&user, &now));
+}
+
+#[test]
+fn test_grant_ex_without_start_stays_paused() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &10_i128, &3600u32);
+
+    let order_id = client.buy_order(&user, &1u32);
+    client.grant_ex(&admin, &user, &order_id, &false);
+
+    let s = client.get_session(&user);
+    assert_eq!(s.started_at, 0);
+    assert_eq!(s.remaining_secs, 3600);
+}
+
+#[test]
+fn test_grant_for_deleted_package_reverts_cleanly() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &10_i128, &3600u32);
+
+    let order_id = client.buy_order(&user, &1u32);
+    client.delete_package(&1u32);
+
+    // erro de contrato tipado, não um pânico opaco
+    let res = client.try_grant(&user, &user, &order_id);
+    assert_eq!(res, Err(Ok(Error::PackageNotFound.into())));
+    assert!(!client.can_grant(&user, &order_id));
+    assert_eq!(client.get_session(&user).remaining_secs, 0);
+}
+
+#[test]
+fn test_start_respects_min_start_secs() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &10_i128, &3600u32);
+    let order_id = client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &order_id);
+
+    client.set_min_start_secs(&600u64);
+    client.start(&user);
+    assert!(client.get_session(&user).started_at > 0);
+
+    // consome até sobrar 300s (< 600) e pausa
+    let t0 = env.ledger().timestamp();
+    env.ledger().with_mut(|li| li.timestamp = t0 + 3300);
+    client.pause(&user);
+    assert_eq!(client.get_session(&user).remaining_secs, 300);
+
+    let res = client.try_start(&user);
+    assert_eq!(res, Err(Ok(Error::BelowMinStart.into())));
+
+    // zero desliga a checagem
+    client.set_min_start_secs(&0u64);
+    client.start(&user);
+    assert!(client.get_session(&user).started_at > 0);
+}
+
+#[test]
+fn test_first_pending_order_returns_lowest_pending() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &10_i128, &3600u32);
+    client.set_package(&2u32, &10_i128, &1800u32);
+
+    assert!(client.first_pending_order(&user).is_none());
+
+    let id1 = client.buy_order(&user, &1u32);
+    let id2 = client.buy_order(&user, &2u32);
+    client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &id1);
+
+    let (id, ord) = client.first_pending_order(&user).unwrap();
+    assert_eq!(id, id2);
+    assert_eq!(ord.package_id, 2);
+    assert!(!ord.credited);
+}
+
+#[test]
+fn test_cap_clamp_emits_cap_hit_event() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &10_i128, &3600u32);
+    client.set_max_session_secs(&5000u64, &true);
+
+    let id1 = client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &id1);
+    let id2 = client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &id2);
+
+    // 3600 + 3600 > 5000 -> só 1400 entram
+    let cap_hit = env
+        .events()
+        .all()
+        .iter()
+        .find(|(_, topics, _)| {
+            without_seq(topics) == (symbol_short!("cap_hit"), user.clone()).into_val(&env)
+        })
+        .unwrap();
+    let (requested, credited): (u64, u64) = cap_hit.2.into_val(&env);
+    assert_eq!((requested, credited), (3600, 1400));
+    assert_eq!(client.get_session(&user).remaining_secs, 5000);
+}
+
+#[test]
+fn test_cap_reject_mode_reverts_grant() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &10_i128, &3600u32);
+    client.set_max_session_secs(&5000u64, &false);
+
+    let id1 = client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &id1);
+    let id2 = client.buy_order(&user, &1u32);
+    let res = client.try_grant(&user, &user, &id2);
+    assert_eq!(res, Err(Ok(Error::SessionCapExceeded.into())));
+    assert!(client.can_grant(&user, &id2));
+}
+
+#[test]
+fn test_metered_session_charges_elapsed_times_rate() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, admin, token_id) = setup(&env, &user);
+    let token = TokenClient::new(&env, &token_id);
+
+    client.set_metered_price(&2_i128, &60u64); // 2 por segundo, mínimo de 60s
+    token.approve(&user, &client.address, &500_i128, &1000u32);
+    client.start_metered(&user);
+    assert!(client.is_metered(&user));
+
+    let t0 = env.ledger().timestamp();
+    env.ledger().with_mut(|li| li.timestamp = t0 + 150);
+    client.pause(&user);
+
+    assert!(!client.is_metered(&user));
+    assert_eq!(token.balance(&admin), 300); // 150s * 2
+    assert_eq!(token.balance(&user), 700);
+    assert_eq!(token.allowance(&user, &client.address), 200);
+}
+
+#[test]
+fn test_metered_session_counts_as_active_and_respects_min_active() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, token_id) = setup(&env, &user);
+    client.set_metered_price(&2_i128, &60u64);
+    client.set_min_active_secs(&30u64);
+    TokenClient::new(&env, &token_id).approve(&user, &client.address, &500_i128, &1000u32);
+
+    let t0 = env.ledger().timestamp();
+    client.start_metered(&user);
+    assert!(client.is_active_now(&user));
+    assert!(client.is_running(&user));
+    assert_eq!(client.active_count(), 1);
+    // allowance de 500 a 2 por segundo cobre 250s
+    assert_eq!(client.ends_at(&user), Some(t0 + 250));
+
+    env.ledger().with_mut(|li| li.timestamp = t0 + 10);
+    assert_eq!(
+        client.try_pause(&user),
+        Err(Ok(Error::TooSoonToPause.into()))
+    );
+    env.ledger().with_mut(|li| li.timestamp = t0 + 30);
+    client.pause(&user);
+    assert!(!client.is_active_now(&user));
+    assert_eq!(client.active_count(), 0);
+    assert_eq!(client.ends_at(&user), None);
+}
+
+#[test]
+fn test_metered_start_requires_allowance_for_minimum() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, token_id) = setup(&env, &user);
+
+    client.set_metered_price(&2_i128, &60u64);
+    TokenClient::new(&env, &token_id).approve(&user, &client.address, &100_i128, &1000u32);
+
+    // mínimo = 120, allowance = 100
+    let res = client.try_start_metered(&user);
+    assert_eq!(res, Err(Ok(Error::InsufficientBalance.into())));
+}
+
+#[test]
+fn test_grant_at_blocks_start_until_not_before() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &10_i128, &3600u32);
+
+    let now = env.ledger().timestamp();
+    let order_id = client.buy_order(&user, &1u32);
+    client.grant_at(&user, &user, &order_id, &(now + 86_400));
+    assert_eq!(client.get_session(&user).not_before, now + 86_400);
+
+    let res = client.try_start(&user);
+    assert_eq!(res, Err(Ok(Error::NotYetAvailable.into())));
+
+    env.ledger().with_mut(|li| li.timestamp = now + 86_400);
+    client.start(&user);
+    assert_eq!(client.get_session(&user).started_at, now + 86_400);
+}
+
+#[test]
+fn test_grant_with_same_ref_credits_once() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &10_i128, &3600u32);
+    let reference = BytesN::from_array(&env, &[7u8; 32]);
+
+    let id1 = client.buy_order(&user, &1u32);
+    assert_eq!(client.grant_with_ref(&user, &user, &id1, &reference), 3600);
+
+    // retry gerou outra ordem: mesma referência não credita de novo
+    let id2 = client.buy_order(&user, &1u32);
+    assert_eq!(client.grant_with_ref(&user, &user, &id2, &reference), 3600);
+    assert_eq!(client.get_session(&user).remaining_secs, 3600);
+    assert!(client.can_grant(&user, &id2));
+}
+
+#[test]
+fn test_ends_at_only_for_running_session() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &10_i128, &3600u32);
+    let order_id = client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &order_id);
+
+    assert_eq!(client.ends_at(&user), None);
+
+    let now = env.ledger().timestamp();
+    client.start(&user);
+    assert_eq!(client.ends_at(&user), Some(now + 3600));
+
+    client.pause(&user);
+    assert_eq!(client.ends_at(&user), None);
+}
+
+#[test]
+fn test_admin_expire_ends_active_session() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &10_i128, &3600u32);
+    let order_id = client.buy_order(&user, &1u32);
+    client.grant_ex(&user, &user, &order_id, &true);
+    assert!(client.is_active_now(&user));
+
+    client.admin_expire(&user);
+    assert!(!client.is_active_now(&user));
+    assert_eq!(client.remaining_now(&user), 0);
+    let s = client.get_session(&user);
+    assert_eq!((s.remaining_secs, s.started_at), (0, 0));
+}
+
+#[test]
+fn test_buy_default_charges_default_package() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, admin, token_id) = setup(&env, &user);
+    client.set_package(&1u32, &10_i128, &3600u32);
+    client.set_package(&2u32, &40_i128, &7200u32);
+
+    let res = client.try_buy_default(&user);
+    assert_eq!(res, Err(Ok(Error::NoDefaultPackage.into())));
+
+    client.set_default_package(&2u32);
+    let order_id = client.buy_default(&user);
+    assert_eq!(TokenClient::new(&env, &token_id).balance(&admin), 40);
+
+    client.grant(&user, &user, &order_id);
+    assert_eq!(client.get_session(&user).remaining_secs, 7200);
+}
+
+#[test]
+fn test_sweep_refunds_expired_pending_orders() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, token_id) = setup(&env, &user);
+    let token = TokenClient::new(&env, &token_id);
+    client.set_escrow_mode(&true);
+    client.set_refund_window(&3600u64);
+    client.set_package(&1u32, &100_i128, &3600u32);
+
+    let id1 = client.buy_order(&user, &1u32);
+    let id2 = client.buy_order(&user, &1u32);
+    let id3 = client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &id2);
+
+    // ainda dentro da janela: nada a reembolsar
+    assert_eq!(client.sweep_refunds(&user, &1u128, &10u32), 0);
+
+    let t0 = env.ledger().timestamp();
+    env.ledger().with_mut(|li| li.timestamp = t0 + 3600);
+    let id4 = client.buy_order(&user, &1u32); // recente, fica de fora
+
+    assert_eq!(client.sweep_refunds(&user, &1u128, &10u32), 2);
+    assert_eq!(token.balance(&user), 1000 - 100 - 100); // id2 creditada + id4 pendente
+    assert!(!client.can_grant(&user, &id1));
+    assert!(!client.can_grant(&user, &id3));
+    assert!(client.can_grant(&user, &id4));
+
+    // segunda passada não reembolsa de novo
+    assert_eq!(client.sweep_refunds(&user, &1u128, &10u32), 0);
+}
+
+#[test]
+fn test_sweep_refunds_from_treasury_when_not_escrowed() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, admin, token_id) = setup(&env, &user);
+    let token = TokenClient::new(&env, &token_id);
+    client.set_refund_window(&60u64);
+    client.set_package(&1u32, &100_i128, &3600u32);
+
+    client.buy_order(&user, &1u32);
+    assert_eq!(token.balance(&admin), 100);
+
+    let t0 = env.ledger().timestamp();
+    env.ledger().with_mut(|li| li.timestamp = t0 + 60);
+    assert_eq!(client.sweep_refunds(&user, &1u128, &10u32), 1);
+    assert_eq!(token.balance(&admin), 0);
+    assert_eq!(token.balance(&user), 1000);
+}
+
+#[test]
+fn test_config_snapshot_reflects_admin_calls() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, admin, token_id) = setup(&env, &user);
+
+    let cfg = client.config();
+    assert_eq!(cfg.admin, admin);
+    assert_eq!(cfg.token, token_id);
+    assert_eq!(cfg.treasury, admin); // padrão
+    assert!(!cfg.paused);
+    assert_eq!(cfg.max_session_secs, 0);
+    assert_eq!(cfg.default_package, None);
+
+    let treasury = Address::generate(&env);
+    client.set_treasury(&treasury);
+    client.set_max_session_secs(&7200u64, &true);
+    client.set_min_start_secs(&60u64);
+    client.set_escrow_mode(&true);
+    client.set_package(&3u32, &10_i128, &3600u32);
+    client.set_default_package(&3u32);
+    client.set_paused(&true);
+
+    let cfg = client.config();
+    assert_eq!(cfg.treasury, treasury);
+    assert_eq!(cfg.max_session_secs, 7200);
+    assert!(cfg.cap_clamp);
+    assert_eq!(cfg.min_start_secs, 60);
+    assert!(cfg.escrow_mode);
+    assert_eq!(cfg.default_package, Some(3));
+    assert!(cfg.paused);
+}
+
+#[test]
+fn test_config_snapshot_covers_session_and_grant_settings() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    let cfg = client.config();
+    assert_eq!(cfg.daily_cap_secs, 0);
+    assert!(!cfg.catalog_frozen);
+    assert!(!cfg.strict_grant);
+
+    client.set_credit_rate(&2_i128);
+    client.set_max_idle_secs(&86_400u64);
+    client.set_min_active_secs(&30u64);
+    client.set_auto_settle(&true);
+    client.set_global_order_mode(&true);
+    client.set_grant_delay_secs(&120u64);
+    client.set_global_grace(&15u64);
+    client.set_daily_cap_secs(&3600u64);
+    client.set_consumption_round_secs(&60u64);
+    client.set_strict_grant(&true);
+    client.freeze_catalog();
+
+    let cfg = client.config();
+    assert_eq!(cfg.credit_per_sec, 2);
+    assert_eq!(cfg.max_idle_secs, 86_400);
+    assert_eq!(cfg.min_active_secs, 30);
+    assert!(cfg.auto_settle);
+    assert!(cfg.global_order_mode);
+    assert_eq!(cfg.grant_delay_secs, 120);
+    assert_eq!(cfg.global_grace_secs, 15);
+    assert_eq!(cfg.daily_cap_secs, 3600);
+    assert_eq!(cfg.consumption_round_secs, 60);
+    assert!(cfg.strict_grant);
+    assert!(cfg.catalog_frozen);
+}
+
+#[test]
+fn test_paused_contract_blocks_buy_and_grant() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &10_i128, &3600u32);
+    let order_id = client.buy_order(&user, &1u32);
+
+    client.set_paused(&true);
+    let res = client.try_buy_order(&user, &1u32);
+    assert_eq!(res, Err(Ok(Error::ContractPaused.into())));
+    let res = client.try_grant(&user, &user, &order_id);
+    assert_eq!(res, Err(Ok(Error::ContractPaused.into())));
+    assert!(!client.can_grant(&user, &order_id));
+
+    client.set_paused(&false);
+    client.grant(&user, &user, &order_id);
+}
+
+#[test]
+fn test_grant_partial_in_two_chunks_closes_order() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &10_i128, &3600u32);
+    let order_id = client.buy_order(&user, &1u32);
+
+    client.grant_partial(&user, &user, &order_id, &1000u64);
+    assert_eq!(client.get_session(&user).remaining_secs, 1000);
+    assert!(client.can_grant(&user, &order_id)); // ainda aberta
+    assert_eq!(client.package_stats(&1u32), (0, 1000));
+
+    // mais do que falta (2600): rejeitado
+    let res = client.try_grant_partial(&user, &user, &order_id, &2601u64);
+    assert_eq!(res, Err(Ok(Error::InvalidOrderState.into())));
+
+    client.grant_partial(&user, &user, &order_id, &2600u64);
+    assert_eq!(client.get_session(&user).remaining_secs, 3600);
+    assert!(!client.can_grant(&user, &order_id));
+    assert_eq!(client.package_stats(&1u32), (1, 3600));
+}
+
+#[test]
+fn test_grant_after_partial_credits_the_rest() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &10_i128, &3600u32);
+    let order_id = client.buy_order(&user, &1u32);
+
+    client.grant_partial(&user, &user, &order_id, &600u64);
+    client.grant(&user, &user, &order_id);
+    assert_eq!(client.get_session(&user).remaining_secs, 3600);
+}
+
+#[test]
+fn test_start_event_carries_projected_end() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &10_i128, &3600u32);
+    let order_id = client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &order_id);
+
+    let now = env.ledger().timestamp();
+    client.start(&user);
+
+    let (_, topics, data) = env.events().all().last().unwrap();
+    let expected: Vec<Val> = (symbol_short!("start"), user.clone()).into_val(&env);
+    assert_eq!(without_seq(&topics), expected);
+    let (started_at, projected_end): (u64, u64) = data.into_val(&env);
+    assert_eq!(started_at, now);
+    assert_eq!(projected_end, now + 3600);
+}
+
+#[test]
+fn test_migrate_legacy_session_to_current_schema() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+
+    // simula uma entrada gravada antes de `not_before` existir
+    env.as_contract(&client.address, || {
+        env.storage().persistent().set(
+            &DataKey::Session(user.clone()),
+            &LegacySession {
+                remaining_secs: 1234,
+                started_at: 0,
+            },
+        );
+    });
+    assert!(client.try_get_session(&user).is_err());
+
+    client.migrate_session(&user);
+    let s = client.get_session(&user);
+    assert_eq!(s.remaining_secs, 1234);
+    assert_eq!(s.started_at, 0);
+    assert_eq!(s.not_before, 0);
+    assert_eq!(client.session_schema(&user), 3);
+
+    // idempotente
+    client.migrate_session(&user);
+    assert_eq!(client.get_session(&user).remaining_secs, 1234);
+}
+
+#[test]
+fn test_legacy_order_is_read_with_default_fields() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+
+    // ordem gravada antes dos campos de pagamento existirem
+    env.as_contract(&client.address, || {
+        env.storage().persistent().set(
+            &DataKey::Order(user.clone(), 1u128),
+            &LegacyOrderRec {
+                package_id: 1,
+                credited: false,
+            },
+        );
+        env.storage()
+            .instance()
+            .set(&DataKey::NextOrder(user.clone()), &1u128);
+    });
+    let rec = client.try_get_order(&user, &1u128).unwrap();
+    assert_eq!(rec.package_id, 1);
+    assert_eq!(rec.price_paid, 0);
+    assert_eq!(rec.created_at, 0);
+    assert_eq!(rec.token, token_id);
+
+    // sem data de compra, o sweep não reembolsa; o grant segue normal
+    client.set_refund_window(&60u64);
+    assert_eq!(client.sweep_refunds(&user, &1u128, &10u32), 0);
+    client.grant(&user, &user, &1u128);
+    assert!(client.try_get_order(&user, &1u128).unwrap().credited);
+    assert_eq!(client.get_session(&user).remaining_secs, 3600);
+}
+
+#[test]
+fn test_buy_order_from_allowance_requires_owner_auth() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, admin, token_id) = setup(&env, &user);
+    let token = TokenClient::new(&env, &token_id);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    token.approve(&user, &client.address, &250_i128, &1000u32);
+
+    // o pagamento sai do allowance; a compra em si é autorizada pelo owner
+    let order_id = client.buy_order_from_allowance(&user, &1u32);
+    assert_eq!(env.auths()[0].0, user);
+    assert_eq!(token.balance(&admin), 100);
+    assert_eq!(token.balance(&user), 900);
+    assert_eq!(token.allowance(&user, &client.address), 150);
+    assert!(client.can_grant(&user, &order_id));
+
+    // sem a assinatura do owner, ninguém gasta o allowance dele
+    env.set_auths(&[]);
+    assert!(client.try_buy_order_from_allowance(&user, &1u32).is_err());
+    assert_eq!(token.allowance(&user, &client.address), 150);
+}
+
+#[test]
+fn test_admin_credit_and_grant_count_separately() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+
+    let order_id = client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &order_id);
+    assert_eq!(client.granted_secs(&GrantSource::Paid), 3600);
+    assert_eq!(client.granted_secs(&GrantSource::Comp), 0);
+
+    assert_eq!(client.admin_credit(&user, &600u64), 4200);
+    let last = env.events().all().last().unwrap();
+    let expected: Vec<Val> = (symbol_short!("comp"), user.clone()).into_val(&env);
+    assert_eq!(without_seq(&last.1), expected);
+
+    assert_eq!(client.granted_secs(&GrantSource::Paid), 3600);
+    assert_eq!(client.granted_secs(&GrantSource::Comp), 600);
+    assert_eq!(client.get_session(&user).remaining_secs, 4200);
+}
+
+#[test]
+fn test_orders_by_status_filters_mixed_orders() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    client.set_escrow_mode(&true);
+
+    let o1 = client.buy_order(&user, &1u32);
+    let o2 = client.buy_order(&user, &1u32);
+    let o3 = client.buy_order(&user, &1u32);
+    let o4 = client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &o1);
+    client.cancel_order(&user, &o2);
+    client.grant(&user, &user, &o4);
+
+    let ids = |status: OrderStatus, start: u128, limit: u32| {
+        let mut v: Vec<u128> = Vec::new(&env);
+        for (id, _) in client
+            .orders_by_status(&user, &status, &start, &limit)
+            .iter()
+        {
+            v.push_back(id);
+        }
+        v
+    };
+    assert_eq!(ids(OrderStatus::Pending, 1, 10), vec![&env, o3]);
+    assert_eq!(ids(OrderStatus::Credited, 1, 10), vec![&env, o1, o4]);
+    assert_eq!(ids(OrderStatus::Refunded, 1, 10), vec![&env, o2]);
+
+    // limit conta resultados filtrados; start pula ids anteriores
+    assert_eq!(ids(OrderStatus::Credited, 1, 1), vec![&env, o1]);
+    assert_eq!(ids(OrderStatus::Credited, 2, 10), vec![&env, o4]);
+}
+
+#[test]
+fn test_reserve_bps_retains_part_of_payment() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, admin, token_id) = setup(&env, &user);
+    let token = TokenClient::new(&env, &token_id);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    client.set_reserve_bps(&2000u32);
+
+    client.buy_order(&user, &1u32);
+    assert_eq!(token.balance(&client.address), 20);
+    assert_eq!(token.balance(&admin), 80);
+    assert_eq!(client.get_reserve_balance(), 20);
+    assert_eq!(client.config().reserve_bps, 2000);
+
+    assert_eq!(
+        client.try_withdraw_reserve(&21_i128),
+        Err(Ok(Error::InsufficientBalance.into()))
+    );
+    client.withdraw_reserve(&15_i128);
+    assert_eq!(token.balance(&client.address), 5);
+    assert_eq!(token.balance(&admin), 95);
+    assert_eq!(client.get_reserve_balance(), 5);
+
+    assert_eq!(
+        client.try_set_reserve_bps(&10_001u32),
+        Err(Ok(Error::InvalidFee.into()))
+    );
+}
+
+#[test]
+fn test_is_package_available_checks_each_condition() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    assert!(!client.is_package_available(&1u32));
+    client.set_package(&1u32, &100_i128, &3600u32);
+    assert!(client.is_package_available(&1u32));
+
+    // desabilitado
+    client.set_package_sale(&1u32, &false, &0u64, &0u64, &None);
+    assert!(!client.is_package_available(&1u32));
+    assert_eq!(
+        client.try_buy_order(&user, &1u32),
+        Err(Ok(Error::PackageUnavailable.into()))
+    );
+
+    // fora da janela (agora = 1_000_000)
+    client.set_package_sale(&1u32, &true, &1_000_100u64, &0u64, &None);
+    assert!(!client.is_package_available(&1u32));
+    client.set_package_sale(&1u32, &true, &0u64, &1_000_000u64, &None);
+    assert!(!client.is_package_available(&1u32));
+    client.set_package_sale(&1u32, &true, &999_000u64, &1_000_100u64, &None);
+    assert!(client.is_package_available(&1u32));
+
+    // esgotado
+    client.set_package_sale(&1u32, &true, &0u64, &0u64, &Some(1u32));
+    assert!(client.is_package_available(&1u32));
+    client.buy_order(&user, &1u32);
+    assert!(!client.is_package_available(&1u32));
+    assert_eq!(
+        client.try_buy_order(&user, &1u32),
+        Err(Ok(Error::PackageUnavailable.into()))
+    );
+
+    // pausado
+    client.set_package_sale(&1u32, &true, &0u64, &0u64, &None);
+    assert!(client.is_package_available(&1u32));
+    client.set_paused(&true);
+    assert!(!client.is_package_available(&1u32));
+}
+
+#[test]
+fn test_refund_unused_returns_unconsumed_share_of_pass() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, admin, token_id) = setup(&env, &user);
+    let token = TokenClient::new(&env, &token_id);
+    client.set_package(&1u32, &100_i128, &3600u32);
+
+    let expires = client.buy_pass(&user, &1u32);
+    assert_eq!(expires, 1_000_000 + 3600);
+    assert_eq!(token.balance(&user), 900);
+    // um passe ativo por vez
+    assert_eq!(
+        client.try_buy_pass(&user, &1u32),
+        Err(Ok(Error::InvalidOrderState.into()))
+    );
+
+    // consome metade
+    env.ledger().with_mut(|li| li.timestamp = 1_000_000 + 1800);
+    assert_eq!(client.refund_unused(&user), 50);
+    assert_eq!(token.balance(&user), 950);
+    assert_eq!(token.balance(&admin), 50);
+    assert!(client.get_pass(&user).is_none());
+    assert_eq!(
+        client.try_refund_unused(&user),
+        Err(Ok(Error::OrderNotFound.into()))
+    );
+}
+
+#[test]
+fn test_event_seq_is_contiguous_across_calls() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+
+    let mut expected = client.event_seq();
+    let mut check = |env: &Env| {
+        for (contract, topics, _) in env.events().all().iter() {
+            if contract != client.address {
+                continue; // eventos do token (SAC)
+            }
+            expected += 1;
+            let seq: u64 = topics.last().unwrap().into_val(env);
+            assert_eq!(seq, expected);
+        }
+    };
+    let order_id = client.buy_order(&user, &1u32);
+    check(&env);
+    client.grant(&user, &user, &order_id);
+    check(&env);
+    client.start(&user);
+    check(&env);
+    client.pause(&user);
+    check(&env);
+    assert_eq!(client.event_seq(), expected);
+}
+
+#[test]
+fn test_admin_credit_many_credits_each_owner() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    let u2 = Address::generate(&env);
+    let u3 = Address::generate(&env);
+    client.admin_credit(&u2, &100u64);
+
+    let owners = vec![&env, user.clone(), u2.clone(), u3.clone()];
+    assert_eq!(client.admin_credit_many(&owners, &600u64), 3);
+    assert_eq!(client.get_session(&user).remaining_secs, 600);
+    assert_eq!(client.get_session(&u2).remaining_secs, 700);
+    assert_eq!(client.get_session(&u3).remaining_secs, 600);
+    assert_eq!(client.granted_secs(&GrantSource::Comp), 100 + 3 * 600);
+
+    let mut big: Vec<Address> = Vec::new(&env);
+    for _ in 0..51 {
+        big.push_back(Address::generate(&env));
+    }
+    assert_eq!(
+        client.try_admin_credit_many(&big, &600u64),
+        Err(Ok(Error::BatchTooLarge.into()))
+    );
+}
+
+#[test]
+fn test_resume_preview_paused_and_running() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.admin_credit(&user, &3600u64);
+
+    // pausada: acompanha o relógio
+    assert_eq!(client.resume_preview(&user), 1_000_000 + 3600);
+    env.ledger().with_mut(|li| li.timestamp = 1_000_500);
+    assert_eq!(client.resume_preview(&user), 1_000_500 + 3600);
+
+    // rodando: fim fixo
+    client.start(&user);
+    env.ledger().with_mut(|li| li.timestamp = 1_001_000);
+    assert_eq!(client.resume_preview(&user), 1_000_500 + 3600);
+    assert_eq!(client.ends_at(&user), Some(client.resume_preview(&user)));
+}
+
+#[test]
+fn test_admin_grant_allowed_flag() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    assert!(client.config().admin_grant_allowed);
+
+    // padrão: admin credita a ordem do usuário
+    let o1 = client.buy_order(&user, &1u32);
+    client.grant(&admin, &user, &o1);
+    assert_eq!(client.get_session(&user).remaining_secs, 3600);
+
+    client.set_admin_grant_allowed(&false);
+    let o2 = client.buy_order(&user, &1u32);
+    assert_eq!(
+        client.try_grant(&admin, &user, &o2),
+        Err(Ok(Error::Unauthorized.into()))
+    );
+    // o próprio owner continua podendo
+    client.grant(&user, &user, &o2);
+    assert_eq!(client.get_session(&user).remaining_secs, 7200);
+}
+
+#[test]
+fn test_active_count_tracks_start_and_pause() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    let u2 = Address::generate(&env);
+    client.admin_credit_many(&vec![&env, user.clone(), u2.clone()], &3600u64);
+    assert_eq!(client.active_count(), 0);
+
+    client.start(&user);
+    client.start(&u2);
+    assert_eq!(client.active_count(), 2);
+    // start repetido não conta de novo
+    client.start(&user);
+    assert_eq!(client.active_count(), 2);
+
+    client.pause(&user);
+    assert_eq!(client.active_count(), 1);
+    client.admin_expire(&u2);
+    assert_eq!(client.active_count(), 0);
+
+    // sessão esgotada some do contador ao ser consolidada
+    client.start(&user);
+    env.ledger().with_mut(|li| li.timestamp += 4000);
+    assert_eq!(client.active_count(), 1);
+    client.pause(&user);
+    assert_eq!(client.active_count(), 0);
+}
+
+#[test]
+fn test_max_open_orders_and_grant_frees_slot() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    client.set_max_open_orders(&2u32);
+
+    let o1 = client.buy_order(&user, &1u32);
+    client.buy_order(&user, &1u32);
+    assert_eq!(client.open_orders(&user), 2);
+    assert_eq!(
+        client.try_buy_order(&user, &1u32),
+        Err(Ok(Error::TooManyOpenOrders.into()))
+    );
+
+    // grant parcial ainda não libera; o grant completo sim
+    client.grant_partial(&user, &user, &o1, &600u64);
+    assert_eq!(client.open_orders(&user), 2);
+    client.grant(&user, &user, &o1);
+    assert_eq!(client.open_orders(&user), 1);
+    client.buy_order(&user, &1u32);
+    assert_eq!(client.open_orders(&user), 2);
+}
+
+#[test]
+fn test_init_native_charges_native_xlm() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let native = env
+        .deployer()
+        .with_stellar_asset(Bytes::from_array(&env, &[0; 4]))
+        .deploy();
+    let admin = fund_native_account(&env, 1, 0);
+    let user = fund_native_account(&env, 2, 1_000);
+
+    let client = AccessTimeClient::new(
+        &env,
+        &env.register(AccessTime, (None::<Address>, None::<Address>)),
+    );
+    client.init_native(&admin);
+    assert_eq!(client.get_token(), native);
+
+    client.set_package(&1u32, &400_i128, &3600u32);
+    let order_id = client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &order_id);
+
+    let xlm = TokenClient::new(&env, &native);
+    assert_eq!(xlm.balance(&user), 600);
+    assert_eq!(xlm.balance(&admin), 400);
+    assert_eq!(client.get_session(&user).remaining_secs, 3600);
+}
+
+#[test]
+fn test_remaining_hms_now_breakdown() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    assert_eq!(client.remaining_hms_now(&user), (0, 0, 0));
+
+    // 2h 3m 4s
+    client.admin_credit(&user, &(2 * 3600 + 3 * 60 + 4));
+    assert_eq!(client.remaining_hms_now(&user), (2, 3, 4));
+
+    client.start(&user);
+    env.ledger().with_mut(|li| li.timestamp += 5);
+    assert_eq!(client.remaining_hms_now(&user), (2, 2, 59));
+}
+
+// Assina a mensagem de `grant_signed` (contrato, owner, order_id, nonce).
+fn sign_grant(
+    env: &Env,
+    contract: &Address,
+    key: &SigningKey,
+    owner: &Address,
+    order_id: u128,
+    nonce: u64,
+) -> BytesN<64> {
+    let mut msg = contract.clone().to_xdr(env);
+    msg.append(&owner.clone().to_xdr(env));
+    msg.extend_from_array(&order_id.to_be_bytes());
+    msg.extend_from_array(&nonce.to_be_bytes());
+    let raw: std::vec::Vec<u8> = msg.iter().collect();
+    BytesN::from_array(env, &key.sign(&raw).to_bytes())
+}
+
+#[test]
+fn test_grant_signed_valid_and_replayed() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+
+    let signer = SigningKey::from_bytes(&[7u8; 32]);
+    let public_key = BytesN::from_array(&env, &signer.verifying_key().to_bytes());
+    client.set_signer_key(&user, &public_key);
+
+    let sign = |order_id: u128, nonce: u64| {
+        sign_grant(&env, &client.address, &signer, &user, order_id, nonce)
+    };
+
+    let o1 = client.buy_order(&user, &1u32);
+    let o2 = client.buy_order(&user, &1u32);
+    // relayer submete sem nenhuma autorização do owner
+    env.mock_auths(&[]);
+    client.grant_signed(&user, &o1, &sign(o1, 0), &0u64);
+    assert_eq!(client.get_session(&user).remaining_secs, 3600);
+
+    // mesmo nonce (mesmo para outra ordem) é replay
+    assert_eq!(
+        client.try_grant_signed(&user, &o2, &sign(o2, 0), &0u64),
+        Err(Ok(Error::NonceReused.into()))
+    );
+    client.grant_signed(&user, &o2, &sign(o2, 1), &1u64);
+    assert_eq!(client.get_session(&user).remaining_secs, 7200);
+}
+
+#[test]
+fn test_delegate_can_start_and_pause_but_not_buy() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    let delegate = Address::generate(&env);
+    let stranger = Address::generate(&env);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    client.admin_credit(&user, &3600u64);
+    client.set_delegate(&user, &Some(delegate.clone()));
+    assert_eq!(client.get_delegate(&user), Some(delegate.clone()));
+
+    // só a assinatura do delegado
+    let only_delegate = |fn_name: &'static str, args: Vec<Val>| {
+        env.mock_auths(&[MockAuth {
+            address: &delegate,
+            invoke: &MockAuthInvoke {
+                contract: &client.address,
+                fn_name,
+                args,
+                sub_invokes: &[],
+            },
+        }]);
+    };
+    only_delegate("start_by", (&delegate, &user).into_val(&env));
+    client.start_by(&delegate, &user);
+    assert!(client.is_active_now(&user));
+
+    only_delegate("pause_by", (&delegate, &user).into_val(&env));
+    client.pause_by(&delegate, &user);
+    assert!(!client.is_active_now(&user));
+
+    // compra exige o owner
+    only_delegate("buy_order", (&user, 1u32).into_val(&env));
+    assert!(client.try_buy_order(&user, &1u32).is_err());
+
+    env.mock_all_auths();
+    assert_eq!(
+        client.try_start_by(&stranger, &user),
+        Err(Ok(Error::Unauthorized.into()))
+    );
+    client.set_delegate(&user, &None);
+    assert_eq!(
+        client.try_start_by(&delegate, &user),
+        Err(Ok(Error::Unauthorized.into()))
+    );
+}
+
+#[test]
+fn test_order_summary_counts_created_and_credited() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    assert_eq!(client.order_summary(&user), (0, 0));
+
+    let o1 = client.buy_order(&user, &1u32);
+    let o2 = client.buy_order(&user, &1u32);
+    client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &o1);
+    // parcial não conta como creditada
+    client.grant_partial(&user, &user, &o2, &60u64);
+    assert_eq!(client.order_summary(&user), (3, 1));
+
+    client.grant(&user, &user, &o2);
+    assert_eq!(client.order_summary(&user), (3, 2));
+}
+
+#[test]
+fn test_buy_time_from_credit_debits_at_rate() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, admin, token_id) = setup(&env, &user);
+    let token = TokenClient::new(&env, &token_id);
+    client.set_credit_rate(&2_i128);
+    client.deposit_credit(&user, &300_i128);
+    assert_eq!(token.balance(&client.address), 300);
+
+    assert_eq!(client.buy_time_from_credit(&user, &100u64), 100);
+    assert_eq!(client.get_credit(&user), 100);
+    assert_eq!(token.balance(&admin), 200);
+
+    // 51 s * 2 = 102 > 100
+    assert_eq!(
+        client.try_buy_time_from_credit(&user, &51u64),
+        Err(Ok(Error::InsufficientBalance.into()))
+    );
+    assert_eq!(client.get_credit(&user), 100);
+    assert_eq!(client.get_session(&user).remaining_secs, 100);
+}
+
+#[test]
+fn test_reset_order_counter_lands_above_highest_order() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    for _ in 0..4 {
+        client.buy_order(&user, &1u32);
+    }
+    // lacunas: ordens 2 e 4 somem (ex.: arquivadas)
+    env.as_contract(&client.address, || {
+        env.storage()
+            .persistent()
+            .remove(&DataKey::Order(user.clone(), 2));
+        env.storage()
+            .persistent()
+            .remove(&DataKey::Order(user.clone(), 4));
+    });
+
+    assert_eq!(client.reset_order_counter(&user), 4);
+    assert_eq!(client.buy_order(&user, &1u32), 4);
+    assert!(client.can_grant(&user, &3));
+}
+
+#[test]
+fn test_fee_on_buy_goes_to_collector_at_purchase() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, admin, token_id) = setup(&env, &user);
+    let token = TokenClient::new(&env, &token_id);
+    let collector = Address::generate(&env);
+    client.set_package(&1u32, &200_i128, &3600u32);
+    client.set_fee(&collector, &1000u32, &FeeTiming::OnBuy);
+
+    let order_id = client.buy_order(&user, &1u32);
+    assert_eq!(token.balance(&collector), 20);
+    assert_eq!(token.balance(&admin), 180);
+    client.grant(&user, &user, &order_id);
+    assert_eq!(token.balance(&collector), 20);
+    assert_eq!(token.balance(&admin), 180);
+}
+
+#[test]
+fn test_fee_on_grant_escrows_then_splits_at_grant() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, admin, token_id) = setup(&env, &user);
+    let token = TokenClient::new(&env, &token_id);
+    let collector = Address::generate(&env);
+    client.set_package(&1u32, &200_i128, &3600u32);
+    client.set_fee(&collector, &1000u32, &FeeTiming::OnGrant);
+
+    let order_id = client.buy_order(&user, &1u32);
+    assert_eq!(token.balance(&client.address), 200);
+    assert_eq!(token.balance(&collector), 0);
+    assert_eq!(token.balance(&admin), 0);
+
+    client.grant(&user, &user, &order_id);
+    assert_eq!(token.balance(&client.address), 0);
+    assert_eq!(token.balance(&collector), 20);
+    assert_eq!(token.balance(&admin), 180);
+
+    // cancelada antes do grant: reembolso integral
+    let o2 = client.buy_order(&user, &1u32);
+    client.cancel_order(&user, &o2);
+    assert_eq!(token.balance(&user), 800);
+    assert_eq!(token.balance(&collector), 20);
+}
+
+#[test]
+fn test_is_running_vs_is_active_now() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    let u2 = Address::generate(&env);
+    client.admin_credit_many(&vec![&env, user.clone(), u2.clone()], &60u64);
+
+    // rodando mas expirada
+    client.start(&user);
+    env.ledger().with_mut(|li| li.timestamp += 120);
+    assert!(client.is_running(&user));
+    assert!(!client.is_active_now(&user));
+
+    // pausada com saldo
+    assert!(!client.is_running(&u2));
+    assert!(!client.is_active_now(&u2));
+    assert_eq!(client.remaining_now(&u2), 60);
+}
+
+#[test]
+fn test_group_session_pools_and_shares_time() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, token_id) = setup(&env, &user);
+    let mate = Address::generate(&env);
+    let outsider = Address::generate(&env);
+    StellarAssetClient::new(&env, &token_id).mint(&mate, &1000_i128);
+    client.set_package(&1u32, &100_i128, &3600u32);
+
+    let team = symbol_short!("team");
+    client.create_group(&user, &team);
+    client.add_member(&user, &team, &mate);
+    assert!(client.is_member(&team, &mate));
+    assert_eq!(
+        client.try_add_member(&mate, &team, &outsider),
+        Err(Ok(Error::Unauthorized.into()))
+    );
+
+    // os dois compram e juntam no saldo do grupo
+    let o1 = client.buy_order(&user, &1u32);
+    let o2 = client.buy_order(&mate, &1u32);
+    client.grant_to_group(&user, &user, &o1, &team);
+    client.grant_to_group(&mate, &mate, &o2, &team);
+    assert_eq!(client.get_group_session(&team).remaining_secs, 7200);
+    assert_eq!(client.get_session(&user).remaining_secs, 0);
+
+    // um inicia, outro pausa: consumo sai do saldo comum
+    client.start_group(&user, &team);
+    env.ledger().with_mut(|li| li.timestamp += 1000);
+    client.pause_group(&mate, &team);
+    assert_eq!(client.get_group_session(&team).remaining_secs, 6200);
+
+    assert_eq!(
+        client.try_start_group(&outsider, &team),
+        Err(Ok(Error::Unauthorized.into()))
+    );
+    client.remove_member(&user, &team, &mate);
+    assert_eq!(
+        client.try_start_group(&mate, &team),
+        Err(Ok(Error::Unauthorized.into()))
+    );
+    assert_eq!(
+        client.try_start_group(&user, &symbol_short!("nope")),
+        Err(Ok(Error::GroupNotFound.into()))
+    );
+}
+
+#[test]
+fn test_withdraw_credit_checks_balance() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, token_id) = setup(&env, &user);
+    let token = TokenClient::new(&env, &token_id);
+    let other = Address::generate(&env);
+    StellarAssetClient::new(&env, &token_id).mint(&other, &500_i128);
+    client.deposit_credit(&user, &300_i128);
+    client.deposit_credit(&other, &500_i128);
+
+    // não saca o crédito dos outros
+    assert_eq!(
+        client.try_withdraw_credit(&user, &301_i128),
+        Err(Ok(Error::InsufficientBalance.into()))
+    );
+    client.withdraw_credit(&user, &300_i128);
+    assert_eq!(client.get_credit(&user), 0);
+    assert_eq!(token.balance(&user), 1000);
+    assert_eq!(
+        client.try_withdraw_credit(&user, &1_i128),
+        Err(Ok(Error::InsufficientBalance.into()))
+    );
+    assert_eq!(client.get_credit(&other), 500);
+    assert_eq!(token.balance(&client.address), 500);
+}
+
+#[test]
+fn test_rounding_policy_parts_sum_to_price() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, token_id) = setup(&env, &user);
+    let token = TokenClient::new(&env, &token_id);
+    let collector = Address::generate(&env);
+    let (a, b, c) = (
+        Address::generate(&env),
+        Address::generate(&env),
+        Address::generate(&env),
+    );
+    client.set_splits(&vec![
+        &env,
+        (a.clone(), 5000u32),
+        (b.clone(), 3000u32),
+        (c.clone(), 2000u32),
+    ]);
+    client.set_fee(&collector, &1000u32, &FeeTiming::OnBuy);
+    client.set_package(&1u32, &103_i128, &3600u32);
+    let balances = || {
+        (
+            token.balance(&collector),
+            token.balance(&a),
+            token.balance(&b),
+            token.balance(&c),
+        )
+    };
+
+    // padrão: taxa 10 (10.3 para baixo); 93 -> 46/27/18 e sobra 2 para `a`
+    client.buy_order(&user, &1u32);
+    let (f, x, y, z) = balances();
+    assert_eq!((f, x, y, z), (10, 48, 27, 18));
+    assert_eq!(f + x + y + z, 103);
+
+    // sobras para o coletor: taxa 11 (para cima); 92 -> 46/27/18 e sobra 1
+    client.set_rounding_to_treasury(&false);
+    client.buy_order(&user, &1u32);
+    let (f2, x2, y2, z2) = balances();
+    assert_eq!((f2 - f, x2 - x, y2 - y, z2 - z), (12, 46, 27, 18));
+    assert_eq!((f2 - f) + (x2 - x) + (y2 - y) + (z2 - z), 103);
+    assert_eq!(token.balance(&user), 1000 - 2 * 103);
+}
+
+#[test]
+fn test_event_schema_version_constant() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    assert_eq!(EVENT_SCHEMA_VERSION, 2);
+    assert_eq!(client.event_schema_version(), EVENT_SCHEMA_VERSION);
+}
+
+#[test]
+fn test_buy_order_max_guards_price() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, token_id) = setup(&env, &user);
+    let token = TokenClient::new(&env, &token_id);
+    client.set_package(&1u32, &100_i128, &3600u32);
+
+    let order_id = client.buy_order_max(&user, &1u32, &100_i128);
+    assert!(client.can_grant(&user, &order_id));
+
+    // preço subiu depois que o usuário assinou
+    client.set_package(&1u32, &120_i128, &3600u32);
+    assert_eq!(
+        client.try_buy_order_max(&user, &1u32, &100_i128),
+        Err(Ok(Error::PriceExceedsMax.into()))
+    );
+    assert_eq!(token.balance(&user), 900);
+}
+
+#[test]
+fn test_admin_repoint_order_then_grant_new_duration() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    client.set_package(&2u32, &100_i128, &7200u32);
+
+    let order_id = client.buy_order(&user, &1u32);
+    assert_eq!(
+        client.try_admin_repoint_order(&user, &order_id, &9u32),
+        Err(Ok(Error::PackageNotFound.into()))
+    );
+    client.admin_repoint_order(&user, &order_id, &2u32);
+    let (_, topics, data) = env.events().all().last().unwrap();
+    let expected: Vec<Val> = (symbol_short!("repoint"), user.clone(), order_id).into_val(&env);
+    assert_eq!(without_seq(&topics), expected);
+    let ids: (u32, u32) = data.into_val(&env);
+    assert_eq!(ids, (1, 2));
+
+    client.grant(&user, &user, &order_id);
+    assert_eq!(client.get_session(&user).remaining_secs, 7200);
+    assert_eq!(
+        client.try_admin_repoint_order(&user, &order_id, &1u32),
+        Err(Ok(Error::AlreadyGranted.into()))
+    );
+}
+
+#[test]
+fn test_time_pass_mint_transfer_redeem() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    let friend = Address::generate(&env);
+    client.admin_credit(&user, &3600u64);
+    client.start(&user);
+    env.ledger().with_mut(|li| li.timestamp += 600);
+
+    let pass_id = client.mint_pass(&user);
+    assert_eq!(client.get_session(&user).remaining_secs, 0);
+    assert!(!client.is_running(&user));
+    assert_eq!(client.get_time_pass(&pass_id).unwrap().secs, 3000);
+
+    client.transfer_pass(&user, &friend, &pass_id);
+    assert_eq!(
+        client.try_redeem_pass(&user, &pass_id),
+        Err(Ok(Error::Unauthorized.into()))
+    );
+    assert_eq!(client.redeem_pass(&friend, &pass_id), 3000);
+    assert_eq!(client.get_session(&friend).remaining_secs, 3000);
+    assert!(client.get_time_pass(&pass_id).is_none());
+    assert_eq!(
+        client.try_redeem_pass(&friend, &pass_id),
+        Err(Ok(Error::PassNotFound.into()))
+    );
+}
+
+#[test]
+fn test_batches_require_a_single_auth() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    let ids = vec![
+        &env,
+        client.buy_order(&user, &1u32),
+        client.buy_order(&user, &1u32),
+        client.buy_order(&user, &1u32),
+    ];
+
+    assert_eq!(client.grant_all(&admin, &user, &ids), 3);
+    let auths = env.auths();
+    assert_eq!(auths.len(), 1);
+    assert_eq!(auths[0].0, admin);
+    assert_eq!(client.get_session(&user).remaining_secs, 3 * 3600);
+
+    let owners = vec![&env, Address::generate(&env), Address::generate(&env)];
+    assert_eq!(client.admin_credit_many(&owners, &60u64), 2);
+    let auths = env.auths();
+    assert_eq!(auths.len(), 1);
+    assert_eq!(auths[0].0, admin);
+}
+
+#[test]
+fn test_package_price_and_duration_scalars() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &250_i128, &7200u32);
+    assert_eq!(client.package_price(&1u32), 250);
+    assert_eq!(client.package_duration(&1u32), 7200);
+    assert_eq!(
+        client.try_package_price(&2u32),
+        Err(Ok(Error::PackageNotFound.into()))
+    );
+    assert_eq!(
+        client.try_package_duration(&2u32),
+        Err(Ok(Error::PackageNotFound.into()))
+    );
+}
+
+#[test]
+fn test_upgrade_order_charges_only_difference() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, admin, token_id) = setup(&env, &user);
+    let token = TokenClient::new(&env, &token_id);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    client.set_package(&2u32, &250_i128, &10_800u32);
+
+    let old = client.buy_order(&user, &1u32);
+    let new = client.upgrade_order(&user, &old, &2u32);
+    assert_eq!(token.balance(&user), 1000 - 250);
+    assert_eq!(token.balance(&admin), 250);
+    assert!(!client.can_grant(&user, &old));
+
+    client.grant(&user, &user, &new);
+    assert_eq!(client.get_session(&user).remaining_secs, 10_800);
+    assert_eq!(client.open_orders(&user), 0);
+
+    let credited = client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &credited);
+    assert_eq!(
+        client.try_upgrade_order(&user, &credited, &2u32),
+        Err(Ok(Error::AlreadyGranted.into()))
+    );
+}
+
+#[test]
+fn test_lazy_expiry_emits_expired_once() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.admin_credit(&user, &60u64);
+    client.start(&user);
+    env.ledger().with_mut(|li| li.timestamp += 120);
+
+    let expired_events = |env: &Env| {
+        env.events()
+            .all()
+            .iter()
+            .filter(|(_, topics, _)| {
+                without_seq(topics) == (symbol_short!("expired"), user.clone()).into_val(env)
+            })
+            .count()
+    };
+    assert_eq!(client.remaining_now(&user), 0);
+    assert_eq!(expired_events(&env), 1);
+    // já notificado: settle não repete
+    client.settle(&user);
+    assert_eq!(expired_events(&env), 0);
+    client.remaining_now(&user);
+    assert_eq!(expired_events(&env), 0);
+    assert!(!client.is_running(&user));
+
+    // nova execução expira de novo -> novo evento, via settle
+    client.admin_credit(&user, &30u64);
+    client.start(&user);
+    env.ledger().with_mut(|li| li.timestamp += 60);
+    client.settle(&user);
+    assert_eq!(expired_events(&env), 1);
+}
+
+#[test]
+fn test_package_grant_cooldown() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &10_i128, &3600u32);
+    client.set_package(&2u32, &10_i128, &3600u32);
+    client.set_package_cooldown(&1u32, &86_400u64);
+
+    let o1 = client.buy_order(&user, &1u32);
+    let o2 = client.buy_order(&user, &1u32);
+    let o3 = client.buy_order(&user, &2u32);
+    client.grant(&user, &user, &o1);
+    assert_eq!(
+        client.try_grant(&user, &user, &o2),
+        Err(Ok(Error::GrantCooldown.into()))
+    );
+    // outro pacote não sofre o cooldown
+    client.grant(&user, &user, &o3);
+
+    env.ledger().with_mut(|li| li.timestamp += 86_400);
+    client.grant(&user, &user, &o2);
+    assert_eq!(client.get_session(&user).remaining_secs, 3 * 3600);
+}
+
+#[test]
+fn test_session_debug_stored_vs_live() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.admin_credit(&user, &3600u64);
+    assert_eq!(client.session_debug(&user), (3600, 3600, 0, false));
+
+    client.start(&user);
+    env.ledger().with_mut(|li| li.timestamp += 100);
+    let (stored, live, started_at, active) = client.session_debug(&user);
+    assert!(stored >= live);
+    assert_eq!((stored, live, started_at), (3600, 3500, 1_000_000));
+    assert_eq!(active, client.is_active_now(&user));
+    assert!(active);
+}
+
+#[test]
+fn test_self_grant_allowed_flag() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    assert!(client.config().self_grant_allowed);
+
+    client.set_self_grant_allowed(&false);
+    let order_id = client.buy_order(&user, &1u32);
+    assert_eq!(
+        client.try_grant(&user, &user, &order_id),
+        Err(Ok(Error::Unauthorized.into()))
+    );
+    client.grant(&admin, &user, &order_id);
+    assert_eq!(client.get_session(&user).remaining_secs, 3600);
+}
+
+#[test]
+fn test_refund_liability_tracks_pending_orders() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_escrow_mode(&true);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    client.set_package(&2u32, &250_i128, &7200u32);
+
+    let o1 = client.buy_order(&user, &1u32);
+    let o2 = client.buy_order(&user, &2u32);
+    let o3 = client.buy_order(&user, &1u32);
+    assert_eq!(client.refund_liability(&user, &1u128, &10u32), 450);
+    assert_eq!(client.refund_liability(&user, &o2, &1u32), 250);
+    assert_eq!(client.open_order_value(), 450);
+
+    client.grant(&user, &user, &o1);
+    client.cancel_order(&user, &o3);
+    assert_eq!(client.refund_liability(&user, &1u128, &10u32), 250);
+    assert_eq!(client.open_order_value(), 250);
+}
+
+#[test]
+fn test_credit_on_running_session_extends_end_exactly() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    let o1 = client.buy_order(&user, &1u32);
+    let o2 = client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &o1);
+    client.start(&user);
+    env.ledger().with_mut(|li| li.timestamp += 1234);
+
+    let before = client.ends_at(&user).unwrap();
+    client.admin_credit(&user, &600u64);
+    assert_eq!(client.ends_at(&user), Some(before + 600));
+
+    env.ledger().with_mut(|li| li.timestamp += 77);
+    client.grant(&user, &user, &o2);
+    assert_eq!(client.ends_at(&user), Some(before + 600 + 3600));
+}
+
+#[test]
+fn test_constructor_initializes_at_deploy() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let user = Address::generate(&env);
+    let token_id = env
+        .register_stellar_asset_contract_v2(admin.clone())
+        .address();
+    let contract_id = env.register(AccessTime, (Some(admin.clone()), Some(token_id.clone())));
+    let client = AccessTimeClient::new(&env, &contract_id);
+
+    assert_eq!(client.get_admin(), admin);
+    assert_eq!(
+        client.try_init(&admin, &token_id),
+        Err(Ok(Error::AlreadyInitialized.into()))
+    );
+
+    StellarAssetClient::new(&env, &token_id).mint(&user, &1000_i128);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    client.buy_order(&user, &1u32);
+    assert_eq!(TokenClient::new(&env, &token_id).balance(&admin), 100);
+}
+
+#[test]
+fn test_list_sessions_returns_named_slots() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    let order_id = client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &order_id);
+
+    client.fund_session(&user, &symbol_short!("phone"), &1000u64);
+    client.fund_session(&user, &symbol_short!("laptop"), &2000u64);
+    client.fund_session(&user, &symbol_short!("phone"), &100u64);
+    client.start_named(&user, &user, &symbol_short!("laptop"));
+
+    let list = client.list_sessions(&user);
+    assert_eq!(list.len(), 2);
+    let (name, phone) = list.get(0).unwrap();
+    assert_eq!(name, symbol_short!("phone"));
+    assert_eq!(phone.remaining_secs, 1100);
+    assert_eq!(phone.started_at, 0);
+    let (name, laptop) = list.get(1).unwrap();
+    assert_eq!(name, symbol_short!("laptop"));
+    assert_eq!(laptop.remaining_secs, 2000);
+    assert!(laptop.started_at > 0);
+    assert_eq!(client.get_session(&user).remaining_secs, 500);
+}
+
+#[test]
+fn test_idle_paused_session_is_rejected_and_forfeited() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    client.set_max_idle_secs(&86_400u64);
+    let order_id = client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &order_id);
+
+    // dentro do limite: nada a confiscar e o start funciona
+    env.ledger().with_mut(|li| li.timestamp += 86_400);
+    assert_eq!(client.forfeit(&user), 0);
+    client.start(&user);
+    env.ledger().with_mut(|li| li.timestamp += 600);
+    client.pause(&user);
+
+    env.ledger().with_mut(|li| li.timestamp += 86_401);
+    assert_eq!(
+        client.try_start(&user),
+        Err(Ok(Error::SessionIdleExpired.into()))
+    );
+    assert_eq!(client.forfeit(&user), 3000);
+    assert_eq!(client.get_session(&user).remaining_secs, 0);
+}
+
+#[test]
+fn test_migrate_v2_session_stamps_last_activity() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    env.as_contract(&client.address, || {
+        env.storage().persistent().set(
+            &DataKey::Session(user.clone()),
+            &SessionV2 {
+                remaining_secs: 500,
+                started_at: 0,
+                not_before: 7,
+            },
+        );
+    });
+    client.migrate_session(&user);
+    let s = client.get_session(&user);
+    assert_eq!(s.remaining_secs, 500);
+    assert_eq!(s.not_before, 7);
+    assert_eq!(s.last_activity, env.ledger().timestamp());
+}
+
+#[test]
+fn test_set_session_raw_is_reflected_by_getters() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    let now = env.ledger().timestamp();
+
+    client.set_session_raw(&user, &3600u64, &(now - 600));
+    assert!(client.is_running(&user));
+    assert_eq!(client.remaining_now(&user), 3000);
+    assert_eq!(client.ends_at(&user), Some(now + 3000));
+    assert_eq!(client.active_count(), 1);
+
+    client.set_session_raw(&user, &100u64, &0u64);
+    assert!(!client.is_running(&user));
+    assert_eq!(client.remaining_now(&user), 100);
+    assert_eq!(client.active_count(), 0);
+}
+
+#[test]
+fn test_next_grantable_returns_order_with_package() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    client.set_package(&2u32, &250_i128, &7200u32);
+    assert_eq!(client.next_grantable(&user), None);
+
+    let o1 = client.buy_order(&user, &1u32);
+    let o2 = client.buy_order(&user, &2u32);
+    assert_eq!(
+        client.next_grantable(&user),
+        Some((o1, 1u32, 3600u32, 100_i128))
+    );
+
+    client.grant(&user, &user, &o1);
+    assert_eq!(
+        client.next_grantable(&user),
+        Some((o2, 2u32, 7200u32, 250_i128))
+    );
+}
+
+#[test]
+fn test_pause_rejected_before_min_active_secs() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    client.set_min_active_secs(&60u64);
+    let order_id = client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &order_id);
+    client.start(&user);
+
+    env.ledger().with_mut(|li| li.timestamp += 59);
+    assert_eq!(
+        client.try_pause(&user),
+        Err(Ok(Error::TooSoonToPause.into()))
+    );
+    assert!(client.is_running(&user));
+
+    env.ledger().with_mut(|li| li.timestamp += 1);
+    client.pause(&user);
+    assert!(!client.is_running(&user));
+    assert_eq!(client.get_session(&user).remaining_secs, 3540);
+}
+
+#[test]
+fn test_grant_to_session_credits_named_slot_only() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    let o1 = client.buy_order(&user, &1u32);
+    let o2 = client.buy_order(&user, &1u32);
+
+    client.grant_to_session(&user, &user, &o1, &symbol_short!("tv"));
+    assert_eq!(client.get_session(&user).remaining_secs, 0);
+    let list = client.list_sessions(&user);
+    assert_eq!(list.len(), 1);
+    let (name, tv) = list.get(0).unwrap();
+    assert_eq!(name, symbol_short!("tv"));
+    assert_eq!(tv.remaining_secs, 3600);
+    assert!(!client.can_grant(&user, &o1));
+
+    client.grant_to_session(&user, &user, &o2, &symbol_short!("default"));
+    assert_eq!(client.get_session(&user).remaining_secs, 3600);
+    assert_eq!(client.list_sessions(&user).len(), 1);
+}
+
+// último evento ("cfg", chave) do contrato: (chave, corpo)
+fn last_cfg_event(env: &Env, client: &AccessTimeClient) -> (Symbol, Val) {
+    let (_, topics, data) = env
+        .events()
+        .all()
+        .iter()
+        .filter(|(c, _, _)| *c == client.address)
+        .last()
+        .unwrap();
+    let topics = without_seq(&topics);
+    assert_eq!(topics.len(), 2);
+    let tag: Symbol = topics.get(0).unwrap().into_val(env);
+    assert_eq!(tag, symbol_short!("cfg"));
+    (topics.get(1).unwrap().into_val(env), data)
+}
+
+#[test]
+fn test_admin_setters_emit_cfg_events() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, admin, _token_id) = setup(&env, &user);
+    let treasury = Address::generate(&env);
+
+    client.set_treasury(&treasury);
+    let (key, data) = last_cfg_event(&env, &client);
+    assert_eq!(key, Symbol::new(&env, "treasury"));
+    let (old, new): (Address, Address) = data.into_val(&env);
+    assert_eq!((old, new), (admin.clone(), treasury.clone()));
+
+    client.set_max_session_secs(&7200u64, &true);
+    let (key, data) = last_cfg_event(&env, &client);
+    assert_eq!(key, Symbol::new(&env, "max_session_secs"));
+    let (old, new): ((u64, bool), (u64, bool)) = data.into_val(&env);
+    assert_eq!((old, new), ((0, false), (7200, true)));
+
+    client.set_paused(&true);
+    let (key, data) = last_cfg_event(&env, &client);
+    assert_eq!(key, Symbol::new(&env, "paused"));
+    let (old, new): (bool, bool) = data.into_val(&env);
+    assert_eq!((old, new), (false, true));
+
+    client.set_fee(&user, &250u32, &FeeTiming::OnGrant);
+    let (key, data) = last_cfg_event(&env, &client);
+    assert_eq!(key, Symbol::new(&env, "fee"));
+    let (old, new): ((Address, u32, FeeTiming), (Address, u32, FeeTiming)) = data.into_val(&env);
+    assert_eq!(old, (treasury, 0, FeeTiming::OnBuy));
+    assert_eq!(new, (user, 250, FeeTiming::OnGrant));
+
+    client.set_min_active_secs(&30u64);
+    let (key, data) = last_cfg_event(&env, &client);
+    assert_eq!(key, Symbol::new(&env, "min_active_secs"));
+    let (old, new): (u64, u64) = data.into_val(&env);
+    assert_eq!((old, new), (0, 30));
+}
+
+#[test]
+fn test_order_remaining_after_partial_grant() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    let order_id = client.buy_order(&user, &1u32);
+    assert_eq!(client.order_remaining(&user, &order_id), 3600);
+
+    client.grant_partial(&user, &user, &order_id, &1000u64);
+    assert_eq!(client.order_remaining(&user, &order_id), 2600);
+
+    client.grant(&user, &user, &order_id);
+    assert_eq!(client.order_remaining(&user, &order_id), 0);
+    assert_eq!(
+        client.try_order_remaining(&user, &99u128),
+        Err(Ok(Error::OrderNotFound.into()))
+    );
+}
+
+#[test]
+fn test_order_remaining_ignores_grant_gates() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    let order_id = client.buy_order(&user, &1u32);
+
+    client.set_grant_delay_secs(&600u64);
+    assert_eq!(client.order_remaining(&user, &order_id), 3600);
+    client.set_paused(&true);
+    assert_eq!(client.order_remaining(&user, &order_id), 3600);
+}
+
+#[test]
+fn test_auto_settle_persists_expiry_on_read() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    let o1 = client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &o1);
+    client.start(&user);
+    env.ledger().with_mut(|li| li.timestamp += 4000);
+
+    // desligado: a leitura não altera o estado gravado
+    assert!(!client.is_active_now(&user));
+    let s = client.get_session(&user);
+    assert!(s.started_at > 0);
+    assert_eq!(s.remaining_secs, 3600);
+    assert_eq!(client.active_count(), 1);
+
+    client.set_auto_settle(&true);
+    let s = client.get_session(&user);
+    assert_eq!((s.remaining_secs, s.started_at), (0, 0));
+    assert!(!client.is_running(&user));
+    assert_eq!(client.active_count(), 0);
+}
+
+#[test]
+fn test_buy_order_bulk_applies_quantity_tiers() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, admin, token_id) = setup(&env, &user);
+    StellarAssetClient::new(&env, &token_id).mint(&user, &10_000_i128);
+    let token = TokenClient::new(&env, &token_id);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    client.set_package_tiers(&1u32, &vec![&env, (5u32, 1000u32), (10u32, 2000u32)]);
+
+    // (quantidade, preço unitário esperado)
+    let bands = [(4u32, 100_i128), (5, 90), (9, 90), (10, 80)];
+    let mut paid = 0;
+    for (qty, unit) in bands {
+        let ids = client.buy_order_bulk(&user, &1u32, &qty);
+        assert_eq!(ids.len(), qty);
+        let first = ids.get(0).unwrap();
+        let (_, ord) = client
+            .orders_by_status(&user, &OrderStatus::Pending, &first, &1u32)
+            .get(0)
+            .unwrap();
+        assert_eq!(ord.price_paid, unit);
+        paid += unit * qty as i128;
+        assert_eq!(token.balance(&admin), paid);
+    }
+
+    assert_eq!(
+        client.try_set_package_tiers(&1u32, &vec![&env, (5u32, 1000u32), (5u32, 2000u32)]),
+        Err(Ok(Error::InvalidFee.into()))
+    );
+}
+
+#[test]
+fn test_require_grant_sig_does_not_consume_nonce() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    let signer = SigningKey::from_bytes(&[9u8; 32]);
+    let sign = |key: &SigningKey, order_id: u128, nonce: u64| {
+        sign_grant(&env, &client.address, key, &user, order_id, nonce)
+    };
+    let o1 = client.buy_order(&user, &1u32);
+    // sem chave registrada nada é válido
+    assert_eq!(
+        client.try_require_grant_sig(&user, &o1, &sign(&signer, o1, 0), &0u64),
+        Err(Ok(Error::Unauthorized.into()))
+    );
+
+    let public_key = BytesN::from_array(&env, &signer.verifying_key().to_bytes());
+    client.set_signer_key(&user, &public_key);
+    let sig = sign(&signer, o1, 0);
+    client.require_grant_sig(&user, &o1, &sig, &0u64);
+    client.require_grant_sig(&user, &o1, &sig, &0u64);
+
+    // assinatura de outra chave ou de outro nonce: o ed25519_verify do host reverte
+    let other = SigningKey::from_bytes(&[3u8; 32]);
+    assert!(client
+        .try_require_grant_sig(&user, &o1, &sign(&other, o1, 0), &0u64)
+        .is_err());
+    assert!(client
+        .try_require_grant_sig(&user, &o1, &sig, &1u64)
+        .is_err());
+
+    client.grant_signed(&user, &o1, &sig, &0u64);
+    assert_eq!(
+        client.try_require_grant_sig(&user, &o1, &sig, &0u64),
+        Err(Ok(Error::NonceReused.into()))
+    );
+}
+
+#[test]
+fn test_refund_uses_token_the_order_was_paid_in() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, admin, old_token) = setup(&env, &user);
+    client.set_escrow_mode(&true);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    let order_id = client.buy_order(&user, &1u32);
+
+    let new_token = env
+        .register_stellar_asset_contract_v2(admin.clone())
+        .address();
+    StellarAssetClient::new(&env, &new_token).mint(&user, &1000_i128);
+    client.set_token(&new_token);
+    assert_eq!(client.get_token(), new_token);
+
+    client.cancel_order(&user, &order_id);
+    assert_eq!(TokenClient::new(&env, &old_token).balance(&user), 1000);
+    assert_eq!(TokenClient::new(&env, &new_token).balance(&user), 1000);
+
+    // compras novas já cobram no token novo
+    client.buy_order(&user, &1u32);
+    assert_eq!(TokenClient::new(&env, &new_token).balance(&user), 900);
+}
+
+#[test]
+fn test_set_token_refused_while_balances_use_current_token() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, admin, _token_id) = setup(&env, &user);
+    let new_token = env
+        .register_stellar_asset_contract_v2(admin.clone())
+        .address();
+
+    client.stake(&user, &50_i128);
+    assert_eq!(
+        client.try_set_token(&new_token),
+        Err(Ok(Error::TokenInUse.into()))
+    );
+    client.unstake(&user, &50_i128);
+
+    client.deposit_credit(&user, &30_i128);
+    assert_eq!(
+        client.try_set_token(&new_token),
+        Err(Ok(Error::TokenInUse.into()))
+    );
+    client.withdraw_credit(&user, &30_i128);
+
+    client.set_package(&1u32, &100_i128, &3600u32);
+    client.buy_pass(&user, &1u32);
+    assert_eq!(
+        client.try_set_token(&new_token),
+        Err(Ok(Error::TokenInUse.into()))
+    );
+    env.ledger().with_mut(|li| li.timestamp += 3600);
+
+    client.set_token(&new_token);
+    assert_eq!(client.get_token(), new_token);
+}
+
+#[test]
+fn test_reserve_is_withdrawn_in_the_token_it_was_retained_in() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, admin, old_token) = setup(&env, &user);
+    client.set_escrow_mode(&true);
+    client.set_reserve_bps(&5000u32);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    let order_id = client.buy_order(&user, &1u32);
+
+    let new_token = env
+        .register_stellar_asset_contract_v2(admin.clone())
+        .address();
+    client.set_token(&new_token);
+    let staker = Address::generate(&env);
+    StellarAssetClient::new(&env, &new_token).mint(&staker, &50_i128);
+    client.stake(&staker, &50_i128);
+
+    // a ordem em escrow retém a reserva no token em que foi paga
+    client.grant(&user, &user, &order_id);
+    assert_eq!(client.get_reserve_balance(), 0);
+    assert_eq!(client.get_reserve_balance_in(&old_token), 50);
+    assert_eq!(
+        client.try_withdraw_reserve(&50_i128),
+        Err(Ok(Error::InsufficientBalance.into()))
+    );
+
+    let before = TokenClient::new(&env, &old_token).balance(&admin);
+    client.withdraw_reserve_in(&old_token, &50_i128);
+    assert_eq!(
+        TokenClient::new(&env, &old_token).balance(&admin),
+        before + 50
+    );
+    assert_eq!(
+        TokenClient::new(&env, &new_token).balance(&client.address),
+        50
+    );
+    assert_eq!(client.get_reserve_balance_in(&old_token), 0);
+}
+
+#[test]
+fn test_admin_grant_batch_across_owners() {
+    let env = Env::default();
+    let alice = Address::generate(&env);
+    let (client, _admin, token_id) = setup(&env, &alice);
+    let bob = Address::generate(&env);
+    let carol = Address::generate(&env);
+    let sac = StellarAssetClient::new(&env, &token_id);
+    sac.mint(&bob, &1000_i128);
+    sac.mint(&carol, &1000_i128);
+    client.set_package(&1u32, &100_i128, &3600u32);
+
+    let a1 = client.buy_order(&alice, &1u32);
+    let b1 = client.buy_order(&bob, &1u32);
+    let c1 = client.buy_order(&carol, &1u32);
+    client.grant(&bob, &bob, &b1);
+
+    let grants = vec![
+        &env,
+        (alice.clone(), a1),
+        (bob.clone(), b1),
+        (carol.clone(), c1),
+    ];
+    assert_eq!(client.admin_grant_batch(&grants), 2);
+    for owner in [&alice, &bob, &carol] {
+        assert_eq!(client.get_session(owner).remaining_secs, 3600);
+    }
+
+    let mut big = Vec::new(&env);
+    for _ in 0..51 {
+        big.push_back((alice.clone(), a1));
+    }
+    assert_eq!(
+        client.try_admin_grant_batch(&big),
+        Err(Ok(Error::BatchTooLarge.into()))
+    );
+}
+
+#[test]
+fn test_guards_reject_consistently_when_paused_or_blocked() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    client.set_metered_price(&1_i128, &60u64);
+    let pass_order = client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &pass_order);
+    let pass_id = client.mint_pass(&user);
+    let order_id = client.buy_order(&user, &1u32);
+    let other = Address::generate(&env);
+    let tv = symbol_short!("tv");
+
+    for (paused, blocked, err) in [
+        (true, false, Error::ContractPaused),
+        (false, true, Error::OwnerBlocked),
+    ] {
+        client.set_paused(&paused);
+        client.set_blocked(&user, &blocked);
+        let want: soroban_sdk::Error = err.into();
+        assert_eq!(client.try_buy_order(&user, &1u32).unwrap_err(), Ok(want));
+        assert_eq!(
+            client.try_grant(&user, &user, &order_id).unwrap_err(),
+            Ok(want)
+        );
+        assert_eq!(client.try_start(&user).unwrap_err(), Ok(want));
+        assert_eq!(client.try_start_metered(&user).unwrap_err(), Ok(want));
+        assert_eq!(
+            client.try_buy_time_from_credit(&user, &60u64).unwrap_err(),
+            Ok(want)
+        );
+        assert_eq!(client.try_mint_pass(&user).unwrap_err(), Ok(want));
+        assert_eq!(
+            client
+                .try_transfer_pass(&user, &other, &pass_id)
+                .unwrap_err(),
+            Ok(want)
+        );
+        assert_eq!(
+            client.try_redeem_pass(&user, &pass_id).unwrap_err(),
+            Ok(want)
+        );
+        assert_eq!(
+            client.try_fund_session(&user, &tv, &60u64).unwrap_err(),
+            Ok(want)
+        );
+        assert_eq!(
+            client.try_start_named(&user, &user, &tv).unwrap_err(),
+            Ok(want)
+        );
+    }
+
+    client.set_blocked(&user, &false);
+    assert!(!client.is_blocked(&user));
+    client.grant(&user, &user, &order_id);
+    client.start(&user);
+    assert!(client.is_running(&user));
+}
+
+#[test]
+fn test_bulk_quote_matches_bulk_charge() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, admin, token_id) = setup(&env, &user);
+    StellarAssetClient::new(&env, &token_id).mint(&user, &10_000_i128);
+    let token = TokenClient::new(&env, &token_id);
+    client.set_package(&1u32, &95_i128, &3600u32);
+    client.set_package_tiers(&1u32, &vec![&env, (3u32, 1500u32)]);
+
+    for qty in [1u32, 3, 7] {
+        let quote = client.bulk_quote(&1u32, &qty);
+        let before = token.balance(&admin);
+        client.buy_order_bulk(&user, &1u32, &qty);
+        assert_eq!(token.balance(&admin) - before, quote);
+    }
+    assert_eq!(client.bulk_quote(&1u32, &3u32), 3 * 81);
+    assert_eq!(
+        client.try_bulk_quote(&1u32, &0u32),
+        Err(Ok(Error::InvalidAmount.into()))
+    );
+    assert_eq!(
+        client.try_bulk_quote(&9u32, &1u32),
+        Err(Ok(Error::PackageNotFound.into()))
+    );
+}
+
+#[test]
+fn test_frozen_catalog_rejects_changes_but_sells() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    client.freeze_catalog();
+    assert!(client.is_catalog_frozen());
+
+    let frozen = Err(Ok(Error::CatalogFrozen.into()));
+    assert_eq!(client.try_set_package(&1u32, &1_i128, &3600u32), frozen);
+    assert_eq!(client.try_set_package(&2u32, &1_i128, &3600u32), frozen);
+    assert_eq!(client.try_delete_package(&1u32), frozen);
+    assert_eq!(
+        client.try_set_packages(&vec![&env, (3u32, 1_i128, 60u32)]),
+        frozen
+    );
+    assert_eq!(
+        client.try_set_package_sale(&1u32, &false, &0u64, &0u64, &None),
+        frozen
+    );
+    assert_eq!(
+        client.try_set_package_tiers(&1u32, &vec![&env, (2u32, 100u32)]),
+        frozen
+    );
+    assert_eq!(client.try_freeze_catalog(), frozen);
+
+    let order_id = client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &order_id);
+    assert_eq!(client.get_session(&user).remaining_secs, 3600);
+}
+
+#[test]
+fn test_global_order_mode_ids_unique_across_owners() {
+    let env = Env::default();
+    let alice = Address::generate(&env);
+    let (client, _admin, token_id) = setup(&env, &alice);
+    let bob = Address::generate(&env);
+    StellarAssetClient::new(&env, &token_id).mint(&bob, &1000_i128);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    client.set_global_order_mode(&true);
+
+    let a1 = client.buy_order(&alice, &1u32);
+    let b1 = client.buy_order(&bob, &1u32);
+    let a2 = client.buy_order(&alice, &1u32);
+    assert_eq!((a1, b1, a2), (1, 2, 3));
+
+    let (owner, ord) = client.global_order(&b1).unwrap();
+    assert_eq!(owner, bob);
+    assert_eq!(ord.price_paid, 100);
+    // a ordem do bob não é vista nem creditável pela alice
+    assert!(!client.can_grant(&alice, &b1));
+    assert_eq!(
+        client.try_grant(&alice, &alice, &b1),
+        Err(Ok(Error::OrderNotFound.into()))
+    );
+
+    client.grant(&bob, &bob, &b1);
+    assert_eq!(client.get_session(&bob).remaining_secs, 3600);
+    assert_eq!(client.order_summary(&alice), (2, 0));
+    assert_eq!(client.order_summary(&bob), (1, 1));
+}
+
+#[test]
+fn test_remaining_at_ts_projects_running_session() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    let order_id = client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &order_id);
+    let now = env.ledger().timestamp();
+
+    // pausada: saldo guardado em qualquer instante
+    assert_eq!(client.remaining_at_ts(&user, &(now + 10_000)), 3600);
+
+    client.start(&user);
+    env.ledger().with_mut(|li| li.timestamp += 600);
+    let now = env.ledger().timestamp();
+    assert_eq!(client.remaining_at_ts(&user, &(now + 1000)), 2000);
+    assert_eq!(client.remaining_at_ts(&user, &(now + 3000)), 0);
+    assert_eq!(client.remaining_at_ts(&user, &(now - 600)), 3000);
+}
+
+#[test]
+fn test_consumed_secs_accumulates_without_double_counting() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    let o1 = client.buy_order(&user, &1u32);
+    let o2 = client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &o1);
+
+    client.start(&user);
+    env.ledger().with_mut(|li| li.timestamp += 300);
+    client.settle(&user);
+    client.settle(&user);
+    assert_eq!(client.consumed_secs(&user), 300);
+    env.ledger().with_mut(|li| li.timestamp += 200);
+    client.pause(&user);
+    client.pause(&user);
+    assert_eq!(client.consumed_secs(&user), 500);
+
+    env.ledger().with_mut(|li| li.timestamp += 10_000);
+    client.start(&user);
+    env.ledger().with_mut(|li| li.timestamp += 100);
+    client.grant(&user, &user, &o2);
+    env.ledger().with_mut(|li| li.timestamp += 50);
+    client.pause(&user);
+    assert_eq!(client.consumed_secs(&user), 650);
+    assert_eq!(client.get_session(&user).remaining_secs, 7200 - 650);
+}
+
+#[test]
+fn test_grant_rejected_before_grant_delay() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    client.set_grant_delay_secs(&900u64);
+    let order_id = client.buy_order(&user, &1u32);
+
+    env.ledger().with_mut(|li| li.timestamp += 899);
+    assert_eq!(
+        client.try_grant(&user, &user, &order_id),
+        Err(Ok(Error::GrantTooEarly.into()))
+    );
+    assert!(!client.can_grant(&user, &order_id));
+
+    env.ledger().with_mut(|li| li.timestamp += 1);
+    client.grant(&user, &user, &order_id);
+    assert_eq!(client.get_session(&user).remaining_secs, 3600);
+}
+
+#[test]
+fn test_global_grace_extends_running_sessions_and_clears() {
+    let env = Env::default();
+    let alice = Address::generate(&env);
+    let (client, _admin, token_id) = setup(&env, &alice);
+    let bob = Address::generate(&env);
+    StellarAssetClient::new(&env, &token_id).mint(&bob, &1000_i128);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    for user in [&alice, &bob] {
+        let order_id = client.buy_order(user, &1u32);
+        client.grant(user, user, &order_id);
+        client.start(user);
+    }
+    env.ledger().with_mut(|li| li.timestamp += 3000);
+
+    client.set_global_grace(&900u64);
+    assert_eq!(client.remaining_now(&alice), 1500);
+    assert_eq!(client.remaining_now(&bob), 1500);
+
+    // dentro da folga, com o saldo real esgotado, segue ativa; o settle grava
+    // o saldo ao vivo
+    env.ledger().with_mut(|li| li.timestamp += 1000);
+    client.settle(&alice);
+    assert!(client.is_active_now(&alice));
+    assert_eq!(client.remaining_now(&alice), 500);
+    assert_eq!(client.get_session(&alice).remaining_secs, 500);
+
+    // zerar a folga só tira de quem ainda não consolidou
+    client.set_global_grace(&0u64);
+    assert_eq!(client.remaining_now(&alice), 500);
+    assert_eq!(client.remaining_now(&bob), 0);
+}
+
+#[test]
+fn test_grace_window_pause_and_mint_use_live_balance() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    let device = Symbol::new(&env, "tv");
+    client.admin_credit(&user, &200u64);
+    client.fund_session(&user, &device, &100u64);
+    client.start_named(&user, &user, &device);
+    client.start(&user);
+    let bob = Address::generate(&env);
+    client.admin_credit(&bob, &100u64);
+    client.start(&bob);
+
+    // saldo 100, 120s corridos, folga 50 -> 30 ao vivo em todas
+    env.ledger().with_mut(|li| li.timestamp += 10);
+    client.set_global_grace(&50u64);
+    env.ledger().with_mut(|li| li.timestamp += 110);
+    client.pause_named(&user, &user, &device);
+    let (_, named) = client.list_sessions(&user).get(0).unwrap();
+    assert_eq!((named.remaining_secs, named.started_at), (30, 0));
+    let pass_id = client.mint_pass(&user);
+    assert_eq!(client.get_time_pass(&pass_id).unwrap().secs, 30);
+    client.pause(&bob);
+    assert_eq!(client.get_session(&bob).remaining_secs, 30);
+
+    // retomar depois do pause não soma a folga de novo
+    client.start(&bob);
+    env.ledger().with_mut(|li| li.timestamp += 10);
+    client.pause(&bob);
+    assert_eq!(client.get_session(&bob).remaining_secs, 20);
+}
+
+#[test]
+fn test_fee_config_reads_fee_and_splits() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, admin, _token_id) = setup(&env, &user);
+    assert_eq!(client.fee_config(), (0u32, admin.clone(), Vec::new(&env)));
+
+    let collector = Address::generate(&env);
+    let a = Address::generate(&env);
+    let b = Address::generate(&env);
+    client.set_fee(&collector, &250u32, &FeeTiming::OnBuy);
+    let splits = vec![&env, (a.clone(), 7000u32), (b.clone(), 3000u32)];
+    client.set_splits(&splits);
+    assert_eq!(client.fee_config(), (250u32, collector, splits));
+}
+
+#[test]
+fn test_ensure_init_fresh_and_matching_reinit() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let token_id = env
+        .register_stellar_asset_contract_v2(admin.clone())
+        .address();
+    let contract_id = env.register(AccessTime, (None::<Address>, None::<Address>));
+    let client = AccessTimeClient::new(&env, &contract_id);
+
+    assert_eq!(client.ensure_init(&admin, &token_id), admin);
+    assert_eq!(client.get_admin(), admin);
+    // mesma configuração: no-op devolvendo o admin existente
+    assert_eq!(client.ensure_init(&admin, &token_id), admin);
+}
+
+#[test]
+fn test_ensure_init_mismatch_reverts() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, admin, token_id) = setup(&env, &user);
+    let other = Address::generate(&env);
+    assert_eq!(
+        client.try_ensure_init(&other, &token_id),
+        Err(Ok(Error::AlreadyInitialized.into()))
+    );
+    assert_eq!(
+        client.try_ensure_init(&admin, &other),
+        Err(Ok(Error::AlreadyInitialized.into()))
+    );
+    assert_eq!(client.get_admin(), admin);
+}
+
+#[test]
+fn test_daily_cap_blocks_start_until_next_day() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    let order_id = client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &order_id);
+    client.set_daily_cap_secs(&600u64);
+
+    client.start(&user);
+    env.ledger().with_mut(|li| li.timestamp += 400);
+    client.pause(&user);
+    client.start(&user);
+    env.ledger().with_mut(|li| li.timestamp += 200);
+    client.pause(&user);
+    assert_eq!(client.daily_usage(&user), 600);
+    assert_eq!(
+        client.try_start(&user),
+        Err(Ok(Error::DailyCapReached.into()))
+    );
+
+    // virada do dia UTC zera o consumo contado
+    let now = env.ledger().timestamp();
+    env.ledger()
+        .with_mut(|li| li.timestamp = (now / 86_400 + 1) * 86_400);
+    assert_eq!(client.daily_usage(&user), 0);
+    client.start(&user);
+    assert!(client.is_active_now(&user));
+}
+
+#[test]
+fn test_daily_cap_limits_run_without_pause() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    let order_id = client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &order_id);
+    client.set_daily_cap_secs(&600u64);
+
+    // a execução roda só o que resta do teto; o resto do saldo fica retido
+    client.start(&user);
+    assert_eq!(client.remaining_now(&user), 600);
+    env.ledger().with_mut(|li| li.timestamp += 300);
+    let order_id = client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &order_id);
+    assert_eq!(client.remaining_now(&user), 300);
+    env.ledger().with_mut(|li| li.timestamp += 1000);
+    assert!(!client.is_active_now(&user));
+    assert_eq!(
+        client.try_start(&user),
+        Err(Ok(Error::DailyCapReached.into()))
+    );
+
+    // parada, o saldo retido (e o crédito recebido no meio) volta intacto
+    client.pause(&user);
+    assert_eq!(client.daily_usage(&user), 600);
+    assert_eq!(client.get_session(&user).remaining_secs, 3600 * 2 - 600);
+    assert_eq!(client.get_session(&user).started_at, 0);
+}
+
+#[test]
+fn test_daily_cap_hold_follows_expire_mint_and_fund() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    client.set_daily_cap_secs(&600u64);
+    let capped_run = |env: &Env| {
+        let order_id = client.buy_order(&user, &1u32);
+        client.grant(&user, &user, &order_id);
+        client.start(&user);
+        env.ledger().with_mut(|li| li.timestamp += 100);
+    };
+
+    // admin_expire encerra também o saldo retido
+    capped_run(&env);
+    client.admin_expire(&user);
+    assert_eq!(client.get_session(&user).remaining_secs, 0);
+
+    // fund_session move o saldo inteiro, retido incluído
+    capped_run(&env);
+    client.fund_session(&user, &symbol_short!("tv"), &1000u64);
+    client.pause(&user);
+    assert_eq!(client.get_session(&user).remaining_secs, 3600 - 100 - 1000);
+
+    // mint_pass leva o saldo retido para o passe e zera a sessão
+    client.start(&user);
+    env.ledger().with_mut(|li| li.timestamp += 100);
+    let pass_id = client.mint_pass(&user);
+    assert_eq!(
+        client.get_time_pass(&pass_id).unwrap().secs,
+        3600 - 200 - 1000
+    );
+    assert_eq!(client.get_session(&user).remaining_secs, 0);
+}
+
+#[test]
+fn test_named_sessions_apply_the_start_gates() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    let tv = symbol_short!("tv");
+
+    // saldo agendado não vai para o slot
+    let now = env.ledger().timestamp();
+    let order_id = client.buy_order(&user, &1u32);
+    client.grant_at(&user, &user, &order_id, &(now + 86_400));
+    assert_eq!(
+        client.try_fund_session(&user, &tv, &600u64),
+        Err(Ok(Error::NotYetAvailable.into()))
+    );
+    env.ledger().with_mut(|li| li.timestamp = now + 86_400);
+
+    // MinStartSecs vale também para o slot
+    client.fund_session(&user, &tv, &30u64);
+    client.set_min_start_secs(&60u64);
+    assert_eq!(
+        client.try_start_named(&user, &user, &tv),
+        Err(Ok(Error::BelowMinStart.into()))
+    );
+    client.set_min_start_secs(&0u64);
+
+    // slot parado além de MaxIdleSecs não volta a rodar
+    client.set_max_idle_secs(&600u64);
+    env.ledger().with_mut(|li| li.timestamp += 601);
+    assert_eq!(
+        client.try_start_named(&user, &user, &tv),
+        Err(Ok(Error::SessionIdleExpired.into()))
+    );
+    client.set_max_idle_secs(&0u64);
+
+    // o fim da execução do slot é notificado uma vez
+    client.start_named(&user, &user, &tv);
+    env.ledger().with_mut(|li| li.timestamp += 31);
+    client.pause_named(&user, &user, &tv);
+    let expired = env.events().all().iter().any(|(_, topics, _)| {
+        topics.get(0).map(|t| Symbol::try_from_val(&env, &t).ok())
+            == Some(Some(symbol_short!("s_expired")))
+    });
+    assert!(expired);
+}
+
+#[test]
+fn test_try_get_order_returns_none_for_missing() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    assert!(client.try_get_order(&user, &1u128).is_none());
+
+    let order_id = client.buy_order(&user, &1u32);
+    let rec = client.try_get_order(&user, &order_id).unwrap();
+    assert_eq!(rec.package_id, 1);
+    assert!(!rec.credited);
+    assert!(client.try_get_order(&user, &(order_id + 1)).is_none());
+}
+
+#[test]
+fn test_purchase_event_flags_sale_window() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    client.set_package(&2u32, &100_i128, &3600u32);
+    let now = env.ledger().timestamp();
+    client.set_package_sale(&1u32, &true, &(now - 10), &(now + 100), &None);
+
+    let on_sale = |env: &Env| -> bool {
+        let (_, _, data) = env
+            .events()
+            .all()
+            .iter()
+            .filter(|(c, t, _)| {
+                let tag: Symbol = t.get(0).unwrap().into_val(env);
+                *c == client.address && tag == Symbol::new(env, "purchase")
+            })
+            .last()
+            .unwrap();
+        let body: (Address, u32, u128, i128, bool) = data.into_val(env);
+        body.4
+    };
+    client.buy_order(&user, &1u32);
+    assert!(on_sale(&env));
+    client.buy_order(&user, &2u32);
+    assert!(!on_sale(&env));
+}
+
+#[test]
+fn test_prebuy_batch_creates_uncredited_orders() {
+    let env = Env::default();
+    let payer = Address::generate(&env);
+    let (client, admin, token_id) = setup(&env, &payer);
+    let token = TokenClient::new(&env, &token_id);
+    client.set_package(&1u32, &100_i128, &3600u32);
+
+    let ids = client.prebuy_batch(&payer, &1u32, &5u32);
+    assert_eq!(ids.len(), 5);
+    assert_eq!(token.balance(&payer), 500);
+    assert_eq!(token.balance(&admin), 500);
+    for id in ids.iter() {
+        let rec = client.try_get_order(&payer, &id).unwrap();
+        assert!(!rec.credited);
+        assert_eq!(rec.price_paid, 100);
+    }
+    assert_eq!(client.remaining_now(&payer), 0);
+}
+
+#[test]
+fn test_affordable_count_from_balance() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &300_i128, &3600u32);
+    client.set_package(&2u32, &0_i128, &3600u32);
+    client.buy_order(&user, &1u32);
+    // saldo 700: paga 2 de 300 e exatamente 3 de 233
+    assert_eq!(client.affordable_count(&user, &1u32), 2);
+    client.set_package(&1u32, &233_i128, &3600u32);
+    assert_eq!(client.affordable_count(&user, &1u32), 3);
+    assert_eq!(client.affordable_count(&user, &2u32), 0);
+    assert_eq!(
+        client.try_affordable_count(&user, &9u32),
+        Err(Ok(Error::PackageNotFound.into()))
+    );
+}
+
+#[test]
+fn test_consumption_rounds_up_to_the_minute() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    let order_id = client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &order_id);
+    client.set_consumption_round_secs(&60u64);
+
+    client.start(&user);
+    env.ledger().with_mut(|li| li.timestamp += 70);
+    client.pause(&user);
+    assert_eq!(client.remaining_now(&user), 3600 - 120);
+
+    // 1 desliga: volta a debitar o tempo exato
+    client.set_consumption_round_secs(&1u64);
+    client.start(&user);
+    env.ledger().with_mut(|li| li.timestamp += 70);
+    client.pause(&user);
+    assert_eq!(client.remaining_now(&user), 3600 - 120 - 70);
+}
+
+#[test]
+fn test_repeated_settle_does_not_compound_rounding() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    let order_id = client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &order_id);
+    client.set_consumption_round_secs(&60u64);
+
+    client.start(&user);
+    for _ in 0..10 {
+        env.ledger().with_mut(|li| li.timestamp += 5);
+        client.settle(&user);
+    }
+    assert_eq!(client.remaining_now(&user), 3600 - 50);
+    client.pause(&user);
+    assert_eq!(client.get_session(&user).remaining_secs, 3600 - 60);
+    assert_eq!(client.consumed_secs(&user), 60);
+}
+
+#[test]
+fn test_export_catalog_returns_config_and_packages() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, admin, token_id) = setup(&env, &user);
+    let treasury = Address::generate(&env);
+    client.set_treasury(&treasury);
+    client.set_escrow_mode(&true);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    client.set_package(&7u32, &250_i128, &7200u32);
+    client.set_package(&3u32, &50_i128, &600u32);
+    client.set_package(&1u32, &120_i128, &3600u32);
+    client.delete_package(&3u32);
+
+    let (cfg, packages) = client.export_catalog();
+    assert_eq!(cfg.admin, admin);
+    assert_eq!(cfg.token, token_id);
+    assert_eq!(cfg.treasury, treasury);
+    assert!(cfg.escrow_mode);
+    assert_eq!(packages.len(), 2);
+    let (id, pkg) = packages.get(0).unwrap();
+    assert_eq!((id, pkg.price, pkg.duration_secs), (1, 120, 3600));
+    let (id, pkg) = packages.get(1).unwrap();
+    assert_eq!((id, pkg.price, pkg.duration_secs), (7, 250, 7200));
+}
+
+#[test]
+fn test_check_invariants_catches_corrupted_session() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    let order_id = client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &order_id);
+    assert!(client.check_invariants(&user));
+    client.start(&user);
+    env.ledger().with_mut(|li| li.timestamp += 600);
+    assert!(client.check_invariants(&user));
+
+    let now = env.ledger().timestamp();
+    // começou no futuro
+    client.set_session_raw(&user, &100u64, &(now + 500));
+    assert!(!client.check_invariants(&user));
+    // rodando com saldo gravado zero
+    client.set_session_raw(&user, &0u64, &(now - 10));
+    assert!(!client.check_invariants(&user));
+    client.set_session_raw(&user, &100u64, &0u64);
+    assert!(client.check_invariants(&user));
+}
+
+#[test]
+fn test_strict_grant_rejects_changed_package() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    client.set_strict_grant(&true);
+
+    env.ledger().with_mut(|li| li.timestamp += 10);
+    let unchanged = client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &unchanged);
+    assert_eq!(client.remaining_now(&user), 3600);
+
+    let order_id = client.buy_order(&user, &1u32);
+    env.ledger().with_mut(|li| li.timestamp += 10);
+    client.set_package(&1u32, &100_i128, &7200u32);
+    assert_eq!(
+        client.try_grant(&user, &user, &order_id),
+        Err(Ok(Error::PackageChanged.into()))
+    );
+    assert!(!client.can_grant(&user, &order_id));
+
+    // fora do modo estrito a mesma ordem volta a ser creditável
+    client.set_strict_grant(&false);
+    client.grant(&user, &user, &order_id);
+}
+
+#[test]
+fn test_open_order_ids_lists_only_pending() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    client.set_escrow_mode(&true);
+    let o1 = client.buy_order(&user, &1u32);
+    let o2 = client.buy_order(&user, &1u32);
+    let o3 = client.buy_order(&user, &1u32);
+    let o4 = client.buy_order(&user, &1u32);
+    client.grant(&user, &user, &o1);
+    client.cancel_order(&user, &o3);
+
+    assert_eq!(
+        client.open_order_ids(&user, &1u128, &10u32),
+        vec![&env, o2, o4]
+    );
+    assert_eq!(client.open_order_ids(&user, &1u128, &1u32), vec![&env, o2]);
+    assert_eq!(
+        client.open_order_ids(&user, &(o2 + 1), &10u32),
+        vec![&env, o4]
+    );
+}
+
+#[test]
+fn test_grant_signature_bound_to_contract_and_owner() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    let signer = SigningKey::from_bytes(&[5u8; 32]);
+    let public_key = BytesN::from_array(&env, &signer.verifying_key().to_bytes());
+    client.set_signer_key(&user, &public_key);
+    let order_id = client.buy_order(&user, &1u32);
+
+    // assinada para outra instância do contrato
+    let other_contract = Address::generate(&env);
+    let sig = sign_grant(&env, &other_contract, &signer, &user, order_id, 0);
+    assert!(client
+        .try_grant_signed(&user, &order_id, &sig, &0u64)
+        .is_err());
+    // assinada para outro owner
+    let bob = Address::generate(&env);
+    let sig = sign_grant(&env, &client.address, &signer, &bob, order_id, 0);
+    assert!(client
+        .try_grant_signed(&user, &order_id, &sig, &0u64)
+        .is_err());
+
+    let sig = sign_grant(&env, &client.address, &signer, &user, order_id, 0);
+    client.grant_signed(&user, &order_id, &sig, &0u64);
+    assert_eq!(client.get_session(&user).remaining_secs, 3600);
+}
+
+#[test]
+fn test_signed_grant_respects_self_grant_toggle() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    let signer = SigningKey::from_bytes(&[6u8; 32]);
+    let public_key = BytesN::from_array(&env, &signer.verifying_key().to_bytes());
+    client.set_signer_key(&user, &public_key);
+    let order_id = client.buy_order(&user, &1u32);
+    let sig = sign_grant(&env, &client.address, &signer, &user, order_id, 0);
+
+    client.set_self_grant_allowed(&false);
+    assert_eq!(
+        client.try_require_grant_sig(&user, &order_id, &sig, &0u64),
+        Err(Ok(Error::Unauthorized.into()))
+    );
+    assert_eq!(
+        client.try_grant_signed(&user, &order_id, &sig, &0u64),
+        Err(Ok(Error::Unauthorized.into()))
+    );
+
+    client.set_self_grant_allowed(&true);
+    client.require_grant_sig(&user, &order_id, &sig, &0u64);
+    client.grant_signed(&user, &order_id, &sig, &0u64);
+}
+
+#[test]
+fn test_package_cooldown_and_sale_emit_cfg_events() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+
+    client.set_package_cooldown(&1u32, &60u64);
+    let (key, data) = last_cfg_event(&env, &client);
+    assert_eq!(key, Symbol::new(&env, "package_cooldown"));
+    let (old, new): ((u32, u64), (u32, u64)) = data.into_val(&env);
+    assert_eq!((old, new), ((1, 0), (1, 60)));
+
+    client.set_package_sale(&1u32, &true, &0u64, &5000u64, &Some(3u32));
+    let (key, data) = last_cfg_event(&env, &client);
+    assert_eq!(key, Symbol::new(&env, "package_sale"));
+    let (old, new): ((u32, Option<SaleRules>), (u32, Option<SaleRules>)) = data.into_val(&env);
+    assert!(old.1.is_none());
+    let rules = new.1.unwrap();
+    assert_eq!((new.0, rules.ends_at, rules.stock), (1, 5000, Some(3)));
+}
+
+#[test]
+fn test_set_package_tiers_emits_cfg_event() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    let tiers = vec![&env, (5u32, 1000u32), (10u32, 2000u32)];
+    client.set_package_tiers(&1u32, &tiers);
+    let (key, data) = last_cfg_event(&env, &client);
+    assert_eq!(key, Symbol::new(&env, "package_tiers"));
+    type Tiers = (u32, Vec<(u32, u32)>);
+    let (old, new): (Tiers, Tiers) = data.into_val(&env);
+    assert_eq!(old, (1, Vec::new(&env)));
+    assert_eq!(new, (1, tiers));
+}
+
+#[test]
+fn test_grant_ref_is_scoped_per_owner() {
+    let env = Env::default();
+    let alice = Address::generate(&env);
+    let (client, _admin, token_id) = setup(&env, &alice);
+    let bob = Address::generate(&env);
+    StellarAssetClient::new(&env, &token_id).mint(&bob, &1000_i128);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    let reference = BytesN::from_array(&env, &[42u8; 32]);
+
+    // bob usa a mesma referência primeiro: não bloqueia a de alice
+    let ob = client.buy_order(&bob, &1u32);
+    assert_eq!(client.grant_with_ref(&bob, &bob, &ob, &reference), 3600);
+    let oa = client.buy_order(&alice, &1u32);
+    let oa2 = client.buy_order(&alice, &1u32);
+    client.grant(&alice, &alice, &oa2);
+    assert_eq!(client.grant_with_ref(&alice, &alice, &oa, &reference), 7200);
+    assert_eq!(client.get_session(&alice).remaining_secs, 7200);
+}
+
+#[test]
+fn test_partially_credited_order_is_not_refundable() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, token_id) = setup(&env, &user);
+    let token = TokenClient::new(&env, &token_id);
+    client.set_escrow_mode(&true);
+    client.set_refund_window(&60u64);
+    client.set_package(&1u32, &100_i128, &3600u32);
+    let order_id = client.buy_order(&user, &1u32);
+    client.grant_partial(&user, &user, &order_id, &1800u64);
+
+    assert_eq!(
+        client.try_cancel_order(&user, &order_id),
+        Err(Ok(Error::InvalidOrderState.into()))
+    );
+    env.ledger().with_mut(|li| li.timestamp += 120);
+    assert_eq!(client.sweep_refunds(&user, &1u128, &10u32), 0);
+    assert_eq!(token.balance(&user), 900);
+    assert_eq!(client.get_session(&user).remaining_secs, 1800);
+}
+
+#[test]
+fn test_min_active_secs_not_reset_by_third_party_settle() {
+    let env = Env::default();
+    let user = Address::generate(&env);
+    let (client, _admin, _token_id) = setup(&env, &user);
+    client.admin_credit(&user, &3600u64);
+    client.set_min_active_secs(&300u64);
+    client.start(&user);
+
+    // settle e crédito com a sessão rodando rebaseiam started_at
+    env.ledger().with_mut(|li| li.timestamp += 200);
+    client.settle(&user);
+    client.admin_credit(&user, &60u64);
+    env.ledger().with_mut(|li| li.timestamp += 100);
+    client.pause(&user);
+    assert_eq!(client.get_session(&user).started_at, 0);
+}